@@ -0,0 +1,135 @@
+use crate::{Interval, Point3, Ray};
+
+/// Axis-aligned bounding box represented by an interval along each of the 3 axes
+#[derive(Clone, Default)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        // Flat objects, e.g. axis-aligned quads, would otherwise get a box with zero thickness
+        // which rays can slip through
+        let delta = 0.0001;
+        let pad = |interval: Interval| {
+            if interval.size() < delta {
+                interval.expand(delta)
+            } else {
+                interval
+            }
+        };
+        Aabb {
+            x: pad(x),
+            y: pad(y),
+            z: pad(z),
+        }
+    }
+
+    /// Create a box using two points as its extremas
+    /// The points can be given in any order
+    pub fn from_points(a: &Point3, b: &Point3) -> Self {
+        let interval = |axis: usize| {
+            if a[axis] <= b[axis] {
+                Interval::new(a[axis], b[axis])
+            } else {
+                Interval::new(b[axis], a[axis])
+            }
+        };
+        Aabb::new(interval(0), interval(1), interval(2))
+    }
+
+    /// Create the tightest box enclosing both the boxes
+    pub fn surrounding(box0: &Aabb, box1: &Aabb) -> Self {
+        Aabb::new(
+            Interval::enclosing(&box0.x, &box1.x),
+            Interval::enclosing(&box0.y, &box1.y),
+            Interval::enclosing(&box0.z, &box1.z),
+        )
+    }
+
+    /// Box which contains nothing
+    pub fn empty() -> Self {
+        Aabb::new(Interval::empty(), Interval::empty(), Interval::empty())
+    }
+
+    /// Box which contains everything
+    pub fn universe() -> Self {
+        Aabb::new(
+            Interval::universe(),
+            Interval::universe(),
+            Interval::universe(),
+        )
+    }
+
+    /// Get the interval along the `n`th axis (0 = x, 1 = y, 2 = z)
+    pub fn axis_interval(&self, n: usize) -> &Interval {
+        match n {
+            1 => &self.y,
+            2 => &self.z,
+            _ => &self.x,
+        }
+    }
+
+    /// Returns the index of the longest axis of the box
+    pub fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() { 0 } else { 2 }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Center of the box
+    pub fn centroid(&self) -> Point3 {
+        Point3::new(
+            0.5 * (self.x.min + self.x.max),
+            0.5 * (self.y.min + self.y.max),
+            0.5 * (self.z.min + self.z.max),
+        )
+    }
+
+    /// Total area of the 6 faces of the box, 0 for an empty box
+    pub fn surface_area(&self) -> f64 {
+        let dx = self.x.size();
+        let dy = self.y.size();
+        let dz = self.z.size();
+        if dx < 0.0 || dy < 0.0 || dz < 0.0 {
+            return 0.0;
+        }
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    /// Returns true if the ray hits the box within `ray_t`
+    ///
+    /// Uses the slab method- the ray hits the box only if the overlap of the `t` intervals
+    /// of the ray inside each pair of axis planes is non-empty
+    pub fn hit(&self, r: &Ray, mut ray_t: Interval) -> bool {
+        let ray_orig = r.origin();
+        let ray_dir = r.direction();
+
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / ray_dir[axis];
+
+            let t0 = (ax.min - ray_orig[axis]) * adinv;
+            let t1 = (ax.max - ray_orig[axis]) * adinv;
+
+            if t0 < t1 {
+                ray_t.min = ray_t.min.max(t0);
+                ray_t.max = ray_t.max.min(t1);
+            } else {
+                ray_t.min = ray_t.min.max(t1);
+                ray_t.max = ray_t.max.min(t0);
+            }
+
+            if ray_t.max <= ray_t.min {
+                return false;
+            }
+        }
+        true
+    }
+}
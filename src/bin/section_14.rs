@@ -1,16 +1,17 @@
 use ray_tracer_in_one_weekend::{
-    Camera, Color, Dielectric, HittableList, Lambertian, Material, Metal, Point3, Sphere, Vec3,
-    utils,
+    BvhNode, Camera, Color, Dielectric, HittableList, Lambertian, Material, Metal, Point3, Sphere,
+    Vec3, utils,
 };
 use std::io::{self, Write};
-use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
 
 fn generate_ppm<T: Write>(output_stream: &mut T) {
     let mut world: HittableList = Default::default();
 
     // Add the ground ball
-    let ground_material = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
-    world.add(Rc::new(Sphere::new(
+    let ground_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(
         Point3::new(0.0, -1000.0, 0.0),
         1000.0,
         ground_material,
@@ -27,47 +28,50 @@ fn generate_ppm<T: Write>(output_stream: &mut T) {
             );
 
             if (center.clone() - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                let sphere_material: Rc<dyn Material> = if choose_mat < 0.8 {
+                let sphere_material: Arc<dyn Material> = if choose_mat < 0.8 {
                     // Diffuse
                     let albedo = Color::random(0.0, 1.0) * Color::random(0.0, 1.0);
-                    Rc::new(Lambertian::new(albedo))
+                    Arc::new(Lambertian::new(albedo))
                 } else if choose_mat < 0.95 {
                     // Metal
                     let albedo = Color::random(0.5, 1.0);
                     let fuzz = utils::random_f64(0.0, 0.5);
-                    Rc::new(Metal::new(albedo, fuzz))
+                    Arc::new(Metal::new(albedo, fuzz))
                 } else {
                     // Glass
-                    Rc::new(Dielectric::new(1.5))
+                    Arc::new(Dielectric::new(1.5))
                 };
 
-                world.add(Rc::new(Sphere::new(center, 0.2, sphere_material)));
+                world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
             }
         }
     }
 
     // Add the 3 big balls
-    let material1 = Rc::new(Dielectric::new(1.5));
-    world.add(Rc::new(Sphere::new(
+    let material1 = Arc::new(Dielectric::new(1.5));
+    world.add(Arc::new(Sphere::new(
         Point3::new(0.0, 1.0, 0.0),
         1.0,
         material1,
     )));
 
-    let material2 = Rc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
-    world.add(Rc::new(Sphere::new(
+    let material2 = Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
+    world.add(Arc::new(Sphere::new(
         Point3::new(-4.0, 1.0, 0.0),
         1.0,
         material2,
     )));
 
-    let material3 = Rc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
-    world.add(Rc::new(Sphere::new(
+    let material3 = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
+    world.add(Arc::new(Sphere::new(
         Point3::new(4.0, 1.0, 0.0),
         1.0,
         material3,
     )));
 
+    // Put all the balls in a bounding volume hierarchy
+    let world = HittableList::new(Arc::new(BvhNode::from_list(world)));
+
     // Set the camera
     let mut cam: Camera = Default::default();
     cam.aspect_ratio = 16.0 / 9.0;
@@ -85,6 +89,8 @@ fn generate_ppm<T: Write>(output_stream: &mut T) {
     cam.defocus_angle = 0.6;
     cam.focus_dist = 10.0;
 
+    cam.threads = thread::available_parallelism().map_or(1, |n| n.get());
+
     cam.render(&world, output_stream);
 }
 
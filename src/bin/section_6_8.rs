@@ -3,7 +3,7 @@ use ray_tracer_in_one_weekend::{
     Color, HitRecord, Hittable, HittableList, Interval, Point3, Ray, Sphere, Vec3,
 };
 use std::io::{self, Write};
-use std::rc::Rc;
+use std::sync::Arc;
 
 fn ray_color(r: Ray, world: &HittableList) -> Color {
     let mut rec: HitRecord = Default::default();
@@ -33,9 +33,9 @@ fn generate_ppm<T: Write>(output_stream: &mut T) {
 
     // World
     let mut world: HittableList = Default::default();
-    world.add(Rc::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5)));
-    world.add(Rc::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0)));
-    // world.add(Rc::new(Sphere::new(Point3::new(0.0, 100.5, -1.0), 100.0)));
+    world.add(Arc::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5)));
+    world.add(Arc::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0)));
+    // world.add(Arc::new(Sphere::new(Point3::new(0.0, 100.5, -1.0), 100.0)));
 
     // Viewport and Camera
     let focal_length: f64 = 1.0;
@@ -0,0 +1,150 @@
+use crate::{Aabb, HitRecord, Hittable, HittableList, Interval, Ray, Sampler};
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+/// Node of a bounding volume hierarchy
+///
+/// Each node stores the box enclosing both of its children, so a ray which misses the box
+/// can skip every object below the node
+pub struct BvhNode {
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    bbox: Aabb,
+}
+
+impl BvhNode {
+    /// Build the hierarchy over all the objects of the `list`
+    pub fn from_list(mut list: HittableList) -> Self {
+        BvhNode::new(&mut list.objects)
+    }
+
+    /// Build the hierarchy over the `objects` by recursively splitting them in half along the
+    /// longest axis of their enclosing box
+    ///
+    /// Panics if `objects` is empty
+    pub fn new(objects: &mut [Arc<dyn Hittable>]) -> Self {
+        assert!(!objects.is_empty(), "BVH needs atleast one object");
+
+        // Build the bounding box of the span of source objects
+        let mut bbox = Aabb::empty();
+        for object in objects.iter() {
+            bbox = Aabb::surrounding(&bbox, &object.bounding_box());
+        }
+        let axis = bbox.longest_axis();
+
+        let (left, right): (Arc<dyn Hittable>, Arc<dyn Hittable>) = match objects.len() {
+            1 => (objects[0].clone(), objects[0].clone()),
+            2 => (objects[0].clone(), objects[1].clone()),
+            object_span => {
+                objects.sort_by(|a, b| BvhNode::box_compare(a, b, axis));
+                let (left_objects, right_objects) = objects.split_at_mut(object_span / 2);
+                (
+                    Arc::new(BvhNode::new(left_objects)),
+                    Arc::new(BvhNode::new(right_objects)),
+                )
+            }
+        };
+
+        BvhNode { left, right, bbox }
+    }
+
+    // Order the objects by the start of their boxes along the `axis`
+    fn box_compare(a: &Arc<dyn Hittable>, b: &Arc<dyn Hittable>, axis: usize) -> Ordering {
+        let a_axis_interval = a.bounding_box().axis_interval(axis).min;
+        let b_axis_interval = b.bounding_box().axis_interval(axis).min;
+        a_axis_interval.total_cmp(&b_axis_interval)
+    }
+}
+
+impl Hittable for BvhNode {
+    /// Find the closest intersection in both the children, skipping them if the ray misses
+    /// the box of this node
+    fn hit(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        if !self.bbox.hit(r, ray_t.clone()) {
+            return false;
+        }
+
+        let hit_left = self.left.hit(r, ray_t.clone(), rec, sampler);
+        // Only look for hits in the right child which are closer than the left child's hit
+        let right_t = Interval::new(ray_t.min, if hit_left { rec.t } else { ray_t.max });
+        let hit_right = self.right.hit(r, right_t, rec, sampler);
+
+        hit_left || hit_right
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox.clone()
+    }
+}
+
+#[cfg(test)]
+mod bvh_tests {
+    use super::*;
+    use crate::{IndependentSampler, Lambertian, Material, Point3, Sphere};
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+    fn random_point(rng: &mut SmallRng, size: f64) -> Point3 {
+        Point3::new(
+            rng.random_range(-size..size),
+            rng.random_range(-size..size),
+            rng.random_range(-size..size),
+        )
+    }
+
+    #[test]
+    fn test_hit_matches_linear_scan() {
+        let mut sampler = IndependentSampler::new(0);
+        let mut rng = SmallRng::seed_from_u64(3);
+        let mat: Arc<dyn Material> = Arc::new(Lambertian::default());
+        for count in [1, 2, 3, 10, 200] {
+            let mut list = HittableList::default();
+            for _ in 0..count {
+                let center = random_point(&mut rng, 10.0);
+                list.add(Arc::new(Sphere::new(
+                    center,
+                    rng.random_range(0.2..2.0),
+                    mat.clone(),
+                )));
+            }
+            let bvh = BvhNode::new(&mut list.objects.clone());
+
+            let (mut hits, mut misses) = (0, 0);
+            for i in 0..400 {
+                let origin = random_point(&mut rng, 15.0);
+                // Aim half of the rays at an object, so there are hits even with one sphere
+                let direction = if i % 2 == 0 {
+                    let target = list.objects[i % count].bounding_box();
+                    let center = Point3::new(
+                        0.5 * (target.x.min + target.x.max),
+                        0.5 * (target.y.min + target.y.max),
+                        0.5 * (target.z.min + target.z.max),
+                    );
+                    &center - &origin
+                } else {
+                    random_point(&mut rng, 1.0).as_vec3().clone()
+                };
+                let r = Ray::new(origin, direction);
+
+                let interval = Interval::new(0.001, f64::INFINITY);
+                let (mut expected, mut actual) = (HitRecord::default(), HitRecord::default());
+                let hit = list.hit(&r, interval.clone(), &mut expected, &mut sampler);
+                assert_eq!(bvh.hit(&r, interval, &mut actual, &mut sampler), hit);
+                if hit {
+                    hits += 1;
+                    assert_eq!(actual.t, expected.t);
+                    assert!((&actual.normal - expected.normal).length() < 1e-12);
+                    assert_eq!(actual.front_face, expected.front_face);
+                } else {
+                    misses += 1;
+                }
+            }
+            assert!(hits > 0 && misses > 0);
+        }
+    }
+}
@@ -1,8 +1,12 @@
 use crate::{
-    Color, HitRecord, Hittable, HittableList, Interval, Point3, Ray, Vec3,
+    Color, Film, HitRecord, Hittable, HittablePdf, Image, ImageFormat, Interval, Onb, Pdf, Point3,
+    Ray, Sampler, SamplerType, ScatterRecord, ToneMapping, Vec3,
     utils::{self, degrees_to_radians},
 };
 use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
 pub struct Camera {
     /// Ratio of image width to image height
@@ -12,7 +16,12 @@ pub struct Camera {
     /// Count of random samples for each pixel
     pub samples_per_pixel: usize,
     /// Max number of ray bounces into the scene
+    ///
+    /// Russian roulette ends most paths well before it, so it can be set very high
     pub max_depth: usize,
+    /// Number of bounces after which paths carrying little light are ended at random
+    /// (Russian roulette), without changing the expected image
+    pub russian_roulette_depth: usize,
     /// Vertical FOV (in degrees)
     pub vfov: f64,
     /// Point camera is looking from
@@ -25,13 +34,32 @@ pub struct Camera {
     pub defocus_angle: f64,
     /// Distance from self.lookfrom point to the plane of perfect focus
     pub focus_dist: f64,
+    /// Color of the light coming from outside the scene
+    pub background: Background,
+    /// Count of worker threads rendering scanlines in parallel (1 renders on the calling thread)
+    pub threads: usize,
+    /// Seed for the random numbers used while rendering; the same seed gives the same image
+    /// for any count of threads
+    pub seed: u64,
+    /// Strategy for picking the random numbers of the pixel offsets, defocus disk samples
+    /// and scattered rays
+    pub sampler: SamplerType,
+    /// Time at which the shutter opens
+    pub shutter_open: f64,
+    /// Time at which the shutter closes; rays are cast at random times in between, blurring
+    /// moving objects
+    pub shutter_close: f64,
+    /// Light emitting objects sampled directly at every diffuse bounce, usually the lights of
+    /// the world gathered in a list; without it lights are only found by chance
+    pub lights: Option<Arc<dyn Hittable>>,
+    /// Exposure and operator turning the rendered radiance into displayable colors, used by
+    /// `render` and `render_image`
+    pub tone_mapping: ToneMapping,
     /// Stream to write the rendered image
     // pub output_stream: Box<dyn Write>,
 
     // Rendered image height
     image_height: usize,
-    // Color scale factor for a sum of pixel samples (is inverse of samples_per_pixel)
-    pixel_samples_scale: f64,
     // Camera center
     camera_center: Point3,
     // Location of pixel (0, 0)
@@ -41,9 +69,7 @@ pub struct Camera {
     // Offset of the pixel in the -y direction
     pixel_delta_v: Vec3,
     // Camera frame basis vectors
-    u: Vec3,
-    v: Vec3,
-    w: Vec3,
+    basis: Onb,
     // Defocus disk horizontal radius vector
     defocus_disk_u: Vec3,
     // Defocus disk vertical radius vector
@@ -58,8 +84,6 @@ impl Camera {
         let image_height_f64 = image_height as f64;
         let image_width_f64 = self.image_width as f64;
 
-        self.pixel_samples_scale = 1.0 / self.samples_per_pixel as f64;
-
         // Camera center
         self.camera_center = self.lookfrom.clone();
 
@@ -71,13 +95,11 @@ impl Camera {
         let vieport_width: f64 = vieport_height * (image_width_f64 / image_height_f64);
 
         // Calculate the u,v,w unit basis vectors for the camera coordinate frame
-        self.w = (&self.lookfrom - &self.lookat).unit_vector();
-        self.u = self.vup.cross(&self.w).unit_vector();
-        self.v = self.w.cross(&self.u);
+        self.basis = Onb::from_w_up(&(&self.lookfrom - &self.lookat), &self.vup);
 
         // Calculate the vectors across the horizontal and down the vertical viewport edges
-        let viewport_u = vieport_width * &self.u;
-        let viewport_v = vieport_height * (-&self.v);
+        let viewport_u = vieport_width * self.basis.u();
+        let viewport_v = vieport_height * (-self.basis.v());
 
         // Calculate the horizontal and vertical delta vectors from pixel to pixel
         self.pixel_delta_u = viewport_u.clone() / image_width_f64;
@@ -85,7 +107,7 @@ impl Camera {
 
         // Calculate the location of the upper left pixel
         let viewport_upper_left = self.camera_center.clone()
-            - self.focus_dist * &self.w
+            - self.focus_dist * self.basis.w()
             - viewport_u / 2.0
             - viewport_v / 2.0;
         self.pixel00_loc =
@@ -94,39 +116,96 @@ impl Camera {
         // Calculate the camera defocus disk basis vectors
         let defocus_radius =
             self.focus_dist * f64::tan(utils::degrees_to_radians(self.defocus_angle / 2.0));
-        self.defocus_disk_u = self.u.clone() * defocus_radius;
-        self.defocus_disk_v = self.v.clone() * defocus_radius;
+        self.defocus_disk_u = self.basis.u().clone() * defocus_radius;
+        self.defocus_disk_v = self.basis.v().clone() * defocus_radius;
+    }
+
+    /// Renders the world to the `output_stream` as a plain PPM image
+    ///
+    /// The world can be any hittable, e.g. a `HittableList` or an acceleration structure built
+    /// over it
+    pub fn render<T: Write>(&mut self, world: &dyn Hittable, output_stream: &mut T) {
+        let image = self.render_image(world);
+        output_stream
+            .write_all(&image.encode(ImageFormat::PlainPpm))
+            .unwrap();
     }
 
-    /// Renders the world to the `output_stream`
-    pub fn render<T: Write>(&mut self, world: &HittableList, output_stream: &mut T) {
+    /// Renders the world to an image, tone mapped and gamma encoded like the output of `render`
+    ///
+    /// The image can then be saved in any supported format, e.g. with `Image::save`
+    pub fn render_image(&mut self, world: &dyn Hittable) -> Image {
+        self.render_film(world).to_image_with(&self.tone_mapping)
+    }
+
+    /// Renders the world to a film holding the linear radiance of every pixel, which can be
+    /// post-processed or combined with other renders before being converted to an image
+    pub fn render_film(&mut self, world: &dyn Hittable) -> Film {
         self.initialise();
 
-        writeln!(
-            output_stream,
-            "P3\n{} {}\n255",
-            self.image_width, self.image_height
-        )
-        .unwrap();
+        let mut film = Film::new(self.image_width, self.image_height);
+        for (j, scanline) in self.render_scanlines(world).into_iter().enumerate() {
+            for (i, sum) in scanline.into_iter().enumerate() {
+                film.add_samples(i, j, sum, self.samples_per_pixel as u32);
+            }
+        }
+        film
+    }
+
+    // Get the sums of the samples of all the pixels of the image, row by row, using every
+    // thread
+    fn render_scanlines(&self, world: &dyn Hittable) -> Vec<Vec<Color>> {
+        if self.threads <= 1 {
+            return (0..self.image_height)
+                .map(|j| self.render_scanline(j, world))
+                .collect();
+        }
+
+        // Workers keep picking the next unrendered scanline until the image is complete
+        let next_scanline = AtomicUsize::new(0);
+        let scanlines = Mutex::new(vec![Vec::new(); self.image_height]);
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| {
+                    loop {
+                        let j = next_scanline.fetch_add(1, Ordering::Relaxed);
+                        if j >= self.image_height {
+                            break;
+                        }
+                        let scanline = self.render_scanline(j, world);
+                        scanlines.lock().unwrap()[j] = scanline;
+                    }
+                });
+            }
+        });
 
-        for j in 0..self.image_height {
-            for i in 0..self.image_width {
+        scanlines.into_inner().unwrap()
+    }
+
+    // Get the sums of the samples of all the pixels in the `j`th row of the image
+    fn render_scanline(&self, j: usize, world: &dyn Hittable) -> Vec<Color> {
+        let mut sampler = self.sampler.make_sampler(self.samples_per_pixel, self.seed);
+
+        (0..self.image_width)
+            .map(|i| {
                 // Sample points around pixels
                 let mut pixel_color = Color::default();
-                for _ in 0..self.samples_per_pixel {
-                    let r: Ray = self.get_ray(i, j);
-                    pixel_color += self.ray_color(&r, self.max_depth, world);
+                for sample_index in 0..self.samples_per_pixel {
+                    // The sampler restarts at every sample, so the row looks the same
+                    // whichever thread renders it
+                    sampler.start_pixel_sample(i, j, sample_index);
+                    let r: Ray = self.get_ray(i, j, sampler.as_mut());
+                    pixel_color += self.ray_color(&r, world, sampler.as_mut());
                 }
-
-                Color::write_color(output_stream, self.pixel_samples_scale * pixel_color);
-            }
-        }
+                pixel_color
+            })
+            .collect()
     }
 
     // Get a ray originating from the camera center (origin) and directed at a
     // randomly sampled point around the pixel location (i, j)
-    fn get_ray(&self, i: usize, j: usize) -> Ray {
-        let offset = Camera::sample_square();
+    fn get_ray(&self, i: usize, j: usize, sampler: &mut dyn Sampler) -> Ray {
+        let offset = Camera::sample_square(sampler);
         let pixel_sample = self.pixel00_loc.clone()
             + ((i as f64 + offset.x()) * self.pixel_delta_u.clone())
             + ((j as f64 + offset.y()) * self.pixel_delta_v.clone());
@@ -134,58 +213,203 @@ impl Camera {
         let ray_origin = if self.defocus_angle <= 0.0 {
             self.camera_center.clone()
         } else {
-            self.defocus_disk_sample()
+            self.defocus_disk_sample(sampler)
         };
         let ray_direction = pixel_sample - ray_origin.clone();
-        Ray::new(ray_origin, ray_direction)
+        // Like the defocus disk, the time only takes a sampler dimension when it's used
+        let ray_time = if self.shutter_close > self.shutter_open {
+            self.shutter_open + (self.shutter_close - self.shutter_open) * sampler.get_1d()
+        } else {
+            self.shutter_open
+        };
+        Ray::with_time(ray_origin, ray_direction, ray_time)
     }
 
     // Returns a random point in the unit square from [-0.5, -0.5] to [0.5, 0.5]
-    fn sample_square() -> Vec3 {
-        Vec3::new(
-            utils::random_f64(0.0, 1.0) - 0.5,
-            utils::random_f64(0.0, 1.0) - 0.5,
-            0.0,
-        )
+    fn sample_square(sampler: &mut dyn Sampler) -> Vec3 {
+        let (x, y) = sampler.get_2d();
+        Vec3::new(x - 0.5, y - 0.5, 0.0)
     }
 
-    fn defocus_disk_sample(&self) -> Point3 {
-        let p = Vec3::random_in_unit_disk();
+    fn defocus_disk_sample(&self, sampler: &mut dyn Sampler) -> Point3 {
+        let p = Vec3::random_in_unit_disk(sampler);
         self.camera_center.clone()
             + (p.x() * self.defocus_disk_u.clone())
             + (p.y() * self.defocus_disk_v.clone())
     }
 
     // Get the color of the closest object in the `world` when passing `ray` through the world
-    fn ray_color(&self, r: &Ray, depth: usize, world: &HittableList) -> Color {
+    //
+    // The path is followed bounce by bounce, keeping the fraction of light it still carries
+    // (its throughput), so the recursion of the book no longer grows the stack with the depth
+    fn ray_color(&self, r: &Ray, world: &dyn Hittable, sampler: &mut dyn Sampler) -> Color {
+        let mut color = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = r.clone();
+        // Density with which the previous bounce sampled the ray, when that bounce also
+        // sampled the lights
+        let mut scatter_pdf: Option<f64> = None;
+
         // If ray bounce limit is exceeded, no more light is gathered
-        if depth == 0 {
+        for depth in 0..self.max_depth {
+            let mut rec: HitRecord = Default::default();
+            // Solve the "Acne Problem"
+            if !world.hit(&ray, Interval::new(0.001, f64::MAX), &mut rec, sampler) {
+                color += throughput * self.background.color(&ray);
+                break;
+            }
+
+            let mut color_from_emission = rec.mat.emitted(&ray, &rec);
+            // The light sampling of the previous bounce could have found this light too, so its
+            // emission is shared between both strategies
+            if let (Some(scatter_pdf), Some(lights)) = (scatter_pdf, &self.lights) {
+                let light_pdf = lights.pdf_value(ray.origin(), ray.direction());
+                color_from_emission = power_heuristic(scatter_pdf, light_pdf) * color_from_emission;
+            }
+            color += throughput.clone() * color_from_emission;
+
+            let mut srec = ScatterRecord::default();
+            if !rec.mat.scatter(&ray, &rec, &mut srec, sampler) {
+                // Ray got absorbed into the material completely, only the emitted light remains
+                break;
+            }
+
+            match srec.pdf {
+                // Specular scattering picks a single direction, there is no density to divide by
+                None => {
+                    throughput = throughput * srec.attenuation;
+                    ray = srec.skip_pdf_ray;
+                    scatter_pdf = None;
+                }
+                Some(pdf) => {
+                    // Light reaching the next bounce is cut off by the depth limit, so the
+                    // lights are only sampled when the scattered ray could reach them too
+                    let lights = self.lights.as_ref().filter(|_| depth + 1 < self.max_depth);
+                    if let Some(lights) = lights {
+                        let color_from_lights = self.sample_lights(
+                            lights.as_ref(),
+                            &ray,
+                            &rec,
+                            &srec.attenuation,
+                            pdf.as_ref(),
+                            world,
+                            sampler,
+                        );
+                        color += throughput.clone() * color_from_lights;
+                    }
+
+                    // Weight the light from the sampled direction by how likely the material is
+                    // to scatter there, over how likely the direction was to be sampled
+                    let scattered =
+                        Ray::with_time(rec.p.clone(), pdf.generate(sampler), ray.time());
+                    let pdf_value = pdf.value(scattered.direction());
+                    if pdf_value <= 0.0 {
+                        break;
+                    }
+                    let scattering_pdf = rec.mat.scattering_pdf(&ray, &rec, &scattered);
+                    throughput = (scattering_pdf / pdf_value) * (throughput * srec.attenuation);
+                    scatter_pdf = lights.map(|_| pdf_value);
+                    ray = scattered;
+                }
+            }
+
+            // Russian roulette: end dim paths at random, and make up for the ones ended by
+            // brightening the surviving ones, which keeps the expected color unchanged
+            if depth + 1 >= self.russian_roulette_depth {
+                let survival = throughput
+                    .r()
+                    .max(throughput.g())
+                    .max(throughput.b())
+                    .min(1.0);
+                if sampler.get_1d() >= survival {
+                    break;
+                }
+                throughput = (1.0 / survival) * throughput;
+            }
+        }
+
+        color
+    }
+
+    // Estimate the light arriving directly from the `lights` at the hit `rec` by sampling a
+    // direction towards them and casting a shadow ray, weighted against the material's own
+    // sampling density `scatter_pdf` with the power heuristic
+    #[allow(clippy::too_many_arguments)]
+    fn sample_lights(
+        &self,
+        lights: &dyn Hittable,
+        r: &Ray,
+        rec: &HitRecord,
+        attenuation: &Color,
+        scatter_pdf: &dyn Pdf,
+        world: &dyn Hittable,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        let light_pdf = HittablePdf::new(lights, &rec.p);
+        let shadow_ray = Ray::with_time(rec.p.clone(), light_pdf.generate(sampler), r.time());
+        let light_pdf_value = light_pdf.value(shadow_ray.direction());
+        let scattering_pdf = rec.mat.scattering_pdf(r, rec, &shadow_ray);
+        if light_pdf_value <= 0.0 || scattering_pdf <= 0.0 {
             return Color::new(0.0, 0.0, 0.0);
         }
-        let mut rec: HitRecord = Default::default();
-        // Solve the "Acne Problem"
-        if world.hit(r, Interval::new(0.001, f64::MAX), &mut rec) {
-            // Simple diffue model-
-            // let direction = Vec3::random_on_hemisphere(&rec.normal);
-
-            // Lambertian Reflection-
-            // let direction = rec.normal + Vec3::random_unit_vector();
-            // return 0.5 * self.ray_color(&Ray::new(rec.p, direction), depth - 1, world);
-
-            let mut scattered = Ray::default();
-            let mut attenuation = Color::default();
-            if rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
-                return attenuation * self.ray_color(&scattered, depth - 1, world);
-            }
-            // Ray got absorbed into the material completely, no reflection happened
+
+        // Whatever the shadow ray hits first is the light it sees, so occluders block the light
+        let mut light_rec: HitRecord = Default::default();
+        if !world.hit(
+            &shadow_ray,
+            Interval::new(0.001, f64::MAX),
+            &mut light_rec,
+            sampler,
+        ) {
             return Color::new(0.0, 0.0, 0.0);
         }
+        let emitted = light_rec.mat.emitted(&shadow_ray, &light_rec);
 
-        let unit_direction = r.direction().unit_vector();
-        let alpha = 0.5 * (unit_direction.y() + 1.0);
-        let white_color = Color::new(1.0, 1.0, 1.0);
-        let blue_color = Color::new(0.5, 0.7, 1.0);
-        (1.0 - alpha) * white_color + alpha * blue_color
+        let weight = power_heuristic(light_pdf_value, scatter_pdf.value(shadow_ray.direction()));
+        (weight * scattering_pdf / light_pdf_value) * (attenuation.clone() * emitted)
+    }
+}
+
+// Weight of a sample taken by a strategy with density `f`, combined with one of density `g`
+fn power_heuristic(f: f64, g: f64) -> f64 {
+    let (f2, g2) = (f * f, g * g);
+    if f2 + g2 == 0.0 { 0.0 } else { f2 / (f2 + g2) }
+}
+
+/// Light coming from the directions in which a ray escapes the scene
+#[derive(Clone)]
+pub enum Background {
+    /// Same color in every direction
+    Solid(Color),
+    /// Blend from the `bottom` color when looking straight down to the `top` color when
+    /// looking straight up
+    Gradient { bottom: Color, top: Color },
+    /// No light, the scene is only lit by its emissive materials
+    Black,
+}
+
+impl Background {
+    /// Get the color seen along the escaping ray `r`
+    pub fn color(&self, r: &Ray) -> Color {
+        match self {
+            Background::Solid(color) => color.clone(),
+            Background::Gradient { bottom, top } => {
+                let unit_direction = r.direction().unit_vector();
+                let alpha = 0.5 * (unit_direction.y() + 1.0);
+                (1.0 - alpha) * bottom.clone() + alpha * top.clone()
+            }
+            Background::Black => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+impl Default for Background {
+    /// White to blue sky
+    fn default() -> Self {
+        Background::Gradient {
+            bottom: Color::new(1.0, 1.0, 1.0),
+            top: Color::new(0.5, 0.7, 1.0),
+        }
     }
 }
 
@@ -196,23 +420,273 @@ impl Default for Camera {
             image_width: 100,
             samples_per_pixel: 10,
             max_depth: 10,
+            russian_roulette_depth: 5,
             vfov: 90.0,
             lookfrom: Point3::new(0.0, 0.0, 0.0),
             lookat: Point3::new(0.0, 0.0, -1.0),
             vup: Vec3::new(0.0, 1.0, 0.0),
             defocus_angle: 0.0,
             focus_dist: 10.0,
+            background: Background::default(),
+            threads: 1,
+            seed: 0,
+            sampler: SamplerType::default(),
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            lights: None,
+            tone_mapping: ToneMapping::default(),
             image_height: Default::default(),
-            pixel_samples_scale: Default::default(),
             camera_center: Default::default(),
             pixel00_loc: Default::default(),
             pixel_delta_u: Default::default(),
             pixel_delta_v: Default::default(),
-            u: Default::default(),
-            v: Default::default(),
-            w: Default::default(),
+            basis: Default::default(),
             defocus_disk_u: Default::default(),
             defocus_disk_v: Default::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod camera_tests {
+    use super::*;
+    use crate::{
+        Dielectric, DiffuseLight, HittableList, Lambertian, Mat4, Metal, Quad, Sphere, Transform,
+    };
+    use std::sync::Arc;
+
+    fn render_test_scene(seed: u64, threads: usize) -> Vec<u8> {
+        let mut world = HittableList::default();
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, -100.5, -1.0),
+            100.0,
+            Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0))),
+        )));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, -1.2),
+            0.5,
+            Arc::new(Dielectric::new(1.5)),
+        )));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(1.0, 0.0, -1.0),
+            0.5,
+            Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.3)),
+        )));
+
+        let mut cam = Camera {
+            image_width: 32,
+            samples_per_pixel: 4,
+            defocus_angle: 2.0,
+            seed,
+            threads,
+            ..Default::default()
+        };
+
+        let mut output_stream = Vec::new();
+        cam.render(&world, &mut output_stream);
+        output_stream
+    }
+
+    #[test]
+    fn test_missed_rays_get_the_background() {
+        // An empty world, so every ray misses
+        let world = HittableList::default();
+        let mut cam = Camera {
+            image_width: 4,
+            samples_per_pixel: 2,
+            background: Background::Solid(Color::new(0.2, 0.4, 0.6)),
+            ..Default::default()
+        };
+        let film = cam.render_film(&world);
+        for pixel in film.pixels() {
+            assert!((pixel.r() - 0.2).abs() < 1e-12);
+            assert!((pixel.g() - 0.4).abs() < 1e-12);
+            assert!((pixel.b() - 0.6).abs() < 1e-12);
+        }
+
+        cam.background = Background::Black;
+        let film = cam.render_film(&world);
+        assert!(
+            film.pixels()
+                .all(|pixel| pixel.r() == 0.0 && pixel.g() == 0.0 && pixel.b() == 0.0)
+        );
+    }
+
+    #[test]
+    fn test_render_is_reproducible() {
+        let single_threaded = render_test_scene(42, 1);
+        assert_eq!(single_threaded, render_test_scene(42, 1));
+        assert_eq!(single_threaded, render_test_scene(42, 3));
+        assert_ne!(single_threaded, render_test_scene(43, 1));
+    }
+
+    #[test]
+    fn test_threaded_film_matches_single_threaded() {
+        let mut world = HittableList::default();
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, -100.5, -1.0),
+            100.0,
+            Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0))),
+        )));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, -1.0),
+            0.5,
+            Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.3)),
+        )));
+
+        let render = |threads| {
+            let mut cam = Camera {
+                image_width: 24,
+                samples_per_pixel: 4,
+                threads,
+                ..Default::default()
+            };
+            cam.render_film(&world)
+        };
+        let single_threaded = render(1);
+        for threads in [2, 5, 64] {
+            let film = render(threads);
+            assert_eq!(film.width(), single_threaded.width());
+            assert_eq!(film.height(), single_threaded.height());
+            for y in 0..film.height() {
+                for x in 0..film.width() {
+                    let (a, b) = (film.pixel(x, y), single_threaded.pixel(x, y));
+                    assert_eq!((a.r(), a.g(), a.b()), (b.r(), b.g(), b.b()));
+                }
+            }
+        }
+    }
+
+    // Average radiance of a diffuse scene lit by a quad light, placed with a transformation
+    fn render_lit_scene(sample_lights: bool) -> Color {
+        let white = Arc::new(Lambertian::new(Color::new(0.7, 0.7, 0.7)));
+        let mut world = HittableList::default();
+        world.add(Arc::new(Quad::new(
+            Point3::new(-2.0, -0.5, 1.0),
+            Vec3::new(4.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -4.0),
+            white.clone(),
+        )));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, -1.0),
+            0.5,
+            white,
+        )));
+        let unit_quad = Arc::new(Quad::new(
+            Point3::new(-0.5, -0.5, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0))),
+        ));
+        let light: Arc<dyn Hittable> = Arc::new(
+            Transform::new(
+                unit_quad,
+                Mat4::translation(&Vec3::new(0.0, 1.5, -1.0))
+                    * Mat4::rotation(&Vec3::new(1.0, 0.0, 0.0), 80.0)
+                    * Mat4::scaling(&Vec3::new(3.0, 0.4, 1.0)),
+            )
+            .unwrap(),
+        );
+        world.add(light.clone());
+
+        let mut cam = Camera {
+            image_width: 12,
+            samples_per_pixel: 800,
+            max_depth: 4,
+            lookfrom: Point3::new(0.0, 0.5, 1.5),
+            lookat: Point3::new(0.0, 0.0, -1.0),
+            background: Background::Black,
+            threads: 4,
+            lights: sample_lights.then_some(light),
+            ..Default::default()
+        };
+        let film = cam.render_film(&world);
+        let count = (film.width() * film.height()) as f64;
+        (1.0 / count)
+            * film
+                .pixels()
+                .fold(Color::default(), |sum, pixel| sum + pixel)
+    }
+
+    #[test]
+    fn test_light_sampling_is_unbiased() {
+        let sampled = render_lit_scene(true).g();
+        let unsampled = render_lit_scene(false).g();
+        assert!(sampled > 0.1);
+        assert!(
+            (sampled - unsampled).abs() < 0.03 * sampled,
+            "{sampled} vs {unsampled}"
+        );
+    }
+
+    // Average radiance inside a closed diffuse sphere lit by a small sphere light
+    fn render_closed_scene(russian_roulette_depth: usize) -> Color {
+        let mut world = HittableList::default();
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            3.0,
+            Arc::new(Lambertian::new(Color::new(0.6, 0.6, 0.6))),
+        )));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, -1.5),
+            0.5,
+            Arc::new(DiffuseLight::new(Color::new(2.0, 2.0, 2.0))),
+        )));
+
+        let mut cam = Camera {
+            image_width: 8,
+            samples_per_pixel: 1500,
+            max_depth: 64,
+            russian_roulette_depth,
+            background: Background::Black,
+            threads: 4,
+            ..Default::default()
+        };
+        let film = cam.render_film(&world);
+        let count = (film.width() * film.height()) as f64;
+        (1.0 / count)
+            * film
+                .pixels()
+                .fold(Color::default(), |sum, pixel| sum + pixel)
+    }
+
+    #[test]
+    fn test_russian_roulette_is_unbiased() {
+        // Ending paths from the first bounce on has the same expected value as never ending them
+        let early = render_closed_scene(1).g();
+        let never = render_closed_scene(64).g();
+        assert!(never > 0.1);
+        assert!((early - never).abs() < 0.03 * never, "{early} vs {never}");
+    }
+
+    #[test]
+    fn test_deep_dielectric_stack_keeps_the_stack_flat() {
+        // Nested glass spheres bounce the rays many times without losing any light, so the
+        // paths only end at the depth limit. A small thread stack catches any recursion
+        let handle = std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(|| {
+                let mut world = HittableList::default();
+                for i in 1..=100 {
+                    world.add(Arc::new(Sphere::new(
+                        Point3::new(0.0, 0.0, 0.0),
+                        0.05 * i as f64,
+                        Arc::new(Dielectric::new(1.5)),
+                    )));
+                }
+                let mut cam = Camera {
+                    image_width: 4,
+                    samples_per_pixel: 1,
+                    max_depth: 5000,
+                    russian_roulette_depth: 5000,
+                    lookfrom: Point3::new(0.0, 0.0, 0.01),
+                    lookat: Point3::new(0.0, 0.0, -1.0),
+                    ..Default::default()
+                };
+                cam.render_film(&world)
+            })
+            .unwrap();
+        let film = handle.join().unwrap();
+        assert!(film.pixels().all(|pixel| pixel.r().is_finite()));
+    }
+}
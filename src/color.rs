@@ -54,6 +54,15 @@ impl Color {
         writeln!(output_stream, "{rbyte} {gbyte} {bbyte}").unwrap();
     }
 
+    /// Apply the same "gamma 2" transformation as `write_color`, without clamping
+    pub fn to_gamma(&self) -> Color {
+        Color::new(
+            Color::linear_to_gamma(self.r()),
+            Color::linear_to_gamma(self.g()),
+            Color::linear_to_gamma(self.b()),
+        )
+    }
+
     // Linear to "gamma 2" transformation
     fn linear_to_gamma(linear_component: f64) -> f64 {
         if linear_component > 0.0 {
@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use crate::{
+    Aabb, Color, HitRecord, Hittable, Interval, Isotropic, Material, Ray, Sampler, Texture, Vec3,
+};
+
+/// Volume of constant density filling the inside of a closed `boundary`, like fog or smoke
+///
+/// A ray going through the volume scatters after a random distance, which follows an
+/// exponential distribution
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    /// The boundary can be any closed surface, e.g. a sphere, a box or a closed mesh
+    pub fn new(boundary: Arc<dyn Hittable>, density: f64, tex: Arc<dyn Texture>) -> Self {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function: Arc::new(Isotropic::from_texture(tex)),
+        }
+    }
+
+    pub fn from_color(boundary: Arc<dyn Hittable>, density: f64, albedo: Color) -> Self {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function: Arc::new(Isotropic::new(albedo)),
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        let ray_length = r.direction().length();
+
+        // Walk the stretches of the ray inside the boundary, so non-convex boundaries work too
+        let mut search_start = f64::NEG_INFINITY;
+        while let Some((enter, exit)) =
+            inside_stretch(self.boundary.as_ref(), r, search_start, sampler)
+        {
+            let t_enter = enter.max(ray_t.min).max(0.0);
+            let t_exit = exit.min(ray_t.max);
+            if t_enter < t_exit {
+                let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+                // 1 - random is in (0, 1], so the logarithm is finite. The distribution is
+                // memoryless, so every stretch can draw a new distance
+                let random = sampler.get_1d();
+                let hit_distance = self.neg_inv_density * (1.0 - random).ln();
+                if hit_distance <= distance_inside_boundary {
+                    rec.t = t_enter + hit_distance / ray_length;
+                    rec.p = r.at(rec.t);
+                    // The volume has no surface, so the normal and side are arbitrary
+                    rec.normal = Vec3::new(1.0, 0.0, 0.0);
+                    rec.front_face = true;
+                    rec.mat = Arc::clone(&self.phase_function);
+                    return true;
+                }
+            }
+
+            if exit >= ray_t.max {
+                break;
+            }
+            search_start = exit + 0.0001;
+        }
+        false
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+/// Find the next stretch of the ray `r` inside the closed `boundary` after `search_start`, as the
+/// ray parameters where it enters and leaves
+///
+/// Starting the search at -infinity handles rays which start inside the boundary
+pub(crate) fn inside_stretch(
+    boundary: &dyn Hittable,
+    r: &Ray,
+    search_start: f64,
+    sampler: &mut dyn Sampler,
+) -> Option<(f64, f64)> {
+    let mut rec1 = HitRecord::default();
+    let mut rec2 = HitRecord::default();
+    if !boundary.hit(
+        r,
+        Interval::new(search_start, f64::INFINITY),
+        &mut rec1,
+        sampler,
+    ) {
+        return None;
+    }
+    if !boundary.hit(
+        r,
+        Interval::new(rec1.t + 0.0001, f64::INFINITY),
+        &mut rec2,
+        sampler,
+    ) {
+        return None;
+    }
+    Some((rec1.t, rec2.t))
+}
+
+#[cfg(test)]
+mod constant_medium_tests {
+    use super::*;
+    use crate::{IndependentSampler, Lambertian, Point3, Sphere};
+
+    #[test]
+    fn test_transmittance_follows_beer_lambert() {
+        let mut sampler = IndependentSampler::new(0);
+        let boundary = Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::default()),
+        ));
+        let medium = ConstantMedium::from_color(boundary, 0.5, Color::new(1.0, 1.0, 1.0));
+
+        // Rays through the center cross 2 units of the medium, starting outside or inside it
+        let ray_count = 20_000;
+        for start in [-5.0, 0.0] {
+            let r = Ray::new(Point3::new(0.0, 0.0, start), Vec3::new(0.0, 0.0, 1.0));
+            let mut transmitted = 0;
+            for _ in 0..ray_count {
+                let mut rec = HitRecord::default();
+                if !medium.hit(
+                    &r,
+                    Interval::new(0.001, f64::INFINITY),
+                    &mut rec,
+                    &mut sampler,
+                ) {
+                    transmitted += 1;
+                }
+            }
+            let expected = if start < 0.0 {
+                (-1.0f64).exp()
+            } else {
+                (-0.5f64).exp()
+            };
+            let fraction = transmitted as f64 / ray_count as f64;
+            assert!(
+                (fraction - expected).abs() < 0.02,
+                "{fraction} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_scattering_follows_the_sampler() {
+        let boundary = Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::default()),
+        ));
+        let medium = ConstantMedium::from_color(boundary, 5.0, Color::new(1.0, 1.0, 1.0));
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit_t = |seed| {
+            let mut sampler = IndependentSampler::new(seed);
+            sampler.start_pixel_sample(3, 4, 5);
+            let mut rec = HitRecord::default();
+            assert!(medium.hit(
+                &r,
+                Interval::new(0.001, f64::INFINITY),
+                &mut rec,
+                &mut sampler
+            ));
+            rec.t
+        };
+        assert_eq!(hit_t(1), hit_t(1));
+        assert_ne!(hit_t(1), hit_t(2));
+    }
+}
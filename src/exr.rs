@@ -0,0 +1,222 @@
+use crate::Film;
+
+/// Type of the samples of the channels of an OpenEXR image
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExrPixelType {
+    /// 16-bit floats, enough for most images at half the size
+    #[default]
+    Half,
+    /// 32-bit floats
+    Float,
+}
+
+/// Encode the linear radiance of `film` as an uncompressed scanline OpenEXR image with R, G and
+/// B channels
+pub fn encode_exr(film: &Film, pixel_type: ExrPixelType) -> Vec<u8> {
+    let (width, height) = (film.width(), film.height());
+    let (type_code, sample_size) = match pixel_type {
+        ExrPixelType::Half => (1, 2),
+        ExrPixelType::Float => (2, 4),
+    };
+
+    // Magic number, then version 2 for a single part scanline image
+    let mut output = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+
+    // Channels are listed in alphabetical order, which is also their order in the scanlines
+    let mut channels = Vec::new();
+    for name in [b'B', b'G', b'R'] {
+        channels.extend_from_slice(&[name, 0]);
+        channels.extend_from_slice(&i32::to_le_bytes(type_code));
+        // Perceptually linear flag and reserved bytes, then x and y sampling
+        channels.extend_from_slice(&[0; 4]);
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+    write_attribute(&mut output, "channels", "chlist", &channels);
+    // No compression
+    write_attribute(&mut output, "compression", "compression", &[0]);
+    let mut window = Vec::new();
+    for value in [0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&i32::to_le_bytes(value));
+    }
+    write_attribute(&mut output, "dataWindow", "box2i", &window);
+    write_attribute(&mut output, "displayWindow", "box2i", &window);
+    // Increasing y
+    write_attribute(&mut output, "lineOrder", "lineOrder", &[0]);
+    write_attribute(
+        &mut output,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    write_attribute(&mut output, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(
+        &mut output,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    output.push(0);
+
+    // Every scanline is a chunk, starting with its row and its size, and the offset table
+    // gives the position of every chunk in the file
+    let chunk_size = 8 + 3 * width * sample_size;
+    let first_chunk = output.len() + 8 * height;
+    for y in 0..height {
+        output.extend_from_slice(&((first_chunk + y * chunk_size) as u64).to_le_bytes());
+    }
+
+    for y in 0..height {
+        let pixels: Vec<_> = (0..width).map(|x| film.pixel(x, y)).collect();
+        output.extend_from_slice(&(y as i32).to_le_bytes());
+        output.extend_from_slice(&((chunk_size - 8) as i32).to_le_bytes());
+        for channel in [2, 1, 0] {
+            for pixel in &pixels {
+                let value = [pixel.r(), pixel.g(), pixel.b()][channel] as f32;
+                match pixel_type {
+                    ExrPixelType::Half => {
+                        output.extend_from_slice(&f32_to_half(value).to_le_bytes())
+                    }
+                    ExrPixelType::Float => output.extend_from_slice(&value.to_le_bytes()),
+                }
+            }
+        }
+    }
+
+    output
+}
+
+fn write_attribute(output: &mut Vec<u8>, name: &str, attribute_type: &str, value: &[u8]) {
+    output.extend_from_slice(name.as_bytes());
+    output.push(0);
+    output.extend_from_slice(attribute_type.as_bytes());
+    output.push(0);
+    output.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    output.extend_from_slice(value);
+}
+
+// Convert to the nearest IEEE 754 half precision float, ties to even
+fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // Infinity, or a quiet NaN
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        // Too large, rounded to infinity
+        return sign | 0x7c00;
+    }
+
+    // Subnormal halves shift the mantissa, including its implicit leading 1, further right
+    let (mantissa, shift) = if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+        (mantissa | 0x80_0000, (14 - half_exponent) as u32)
+    } else {
+        (mantissa, 13)
+    };
+    let mut half = mantissa >> shift;
+    let remainder = mantissa & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    if remainder > halfway || (remainder == halfway && half & 1 == 1) {
+        // May carry into the exponent, which gives the right result
+        half += 1;
+    }
+
+    let exponent_bits = if half_exponent > 0 {
+        (half_exponent as u32) << 10
+    } else {
+        0
+    };
+    sign | (exponent_bits + half) as u16
+}
+
+#[cfg(test)]
+mod exr_tests {
+    use super::*;
+    use crate::Color;
+
+    fn read_i32(bytes: &[u8], offset: usize) -> i32 {
+        i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    // Position of the offset table, after the null terminated header attributes
+    fn skip_header(bytes: &[u8]) -> usize {
+        let read_string = |offset: usize| {
+            let end = offset + bytes[offset..].iter().position(|&b| b == 0).unwrap();
+            (&bytes[offset..end], end + 1)
+        };
+        let mut offset = 8;
+        loop {
+            let (name, next) = read_string(offset);
+            if name.is_empty() {
+                return next;
+            }
+            let (_, next) = read_string(next);
+            offset = next + 4 + read_i32(bytes, next) as usize;
+        }
+    }
+
+    #[test]
+    fn test_offset_table_points_to_the_scanlines() {
+        let (width, height) = (3, 4);
+        let mut film = Film::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let value = (10 * y + x) as f64;
+                film.add_sample(x, y, Color::new(value, value + 0.25, value + 0.5));
+            }
+        }
+        let bytes = encode_exr(&film, ExrPixelType::Float);
+        assert_eq!(bytes[..4], [0x76, 0x2f, 0x31, 0x01]);
+
+        let table = skip_header(&bytes);
+        let chunk_size = 8 + 3 * width * 4;
+        for y in 0..height {
+            let entry = table + 8 * y;
+            let offset = u64::from_le_bytes(bytes[entry..entry + 8].try_into().unwrap()) as usize;
+            // Every chunk starts with its y and its size, then the B, G and R samples of its row
+            assert_eq!(read_i32(&bytes, offset), y as i32);
+            assert_eq!(read_i32(&bytes, offset + 4), (chunk_size - 8) as i32);
+            for x in 0..width {
+                let value = (10 * y + x) as f32;
+                assert_eq!(read_f32(&bytes, offset + 8 + 4 * x), value + 0.5);
+                assert_eq!(read_f32(&bytes, offset + 8 + 4 * (width + x)), value + 0.25);
+                assert_eq!(read_f32(&bytes, offset + 8 + 4 * (2 * width + x)), value);
+            }
+            if y == height - 1 {
+                assert_eq!(offset + chunk_size, bytes.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_f32_to_half() {
+        assert_eq!(f32_to_half(0.0), 0x0000);
+        assert_eq!(f32_to_half(-0.0), 0x8000);
+        assert_eq!(f32_to_half(1.0), 0x3c00);
+        assert_eq!(f32_to_half(-2.0), 0xc000);
+        assert_eq!(f32_to_half(0.1), 0x2e66);
+        assert_eq!(f32_to_half(65504.0), 0x7bff);
+        // Rounded up past the largest half
+        assert_eq!(f32_to_half(65520.0), 0x7c00);
+        assert_eq!(f32_to_half(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_half(f32::NAN) & 0x7e00, 0x7e00);
+        // Smallest subnormal, and a tie rounded to even
+        assert_eq!(f32_to_half(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_half(2f32.powi(-25)), 0x0000);
+        assert_eq!(f32_to_half(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+    }
+}
@@ -0,0 +1,194 @@
+use crate::{Color, ExrPixelType, Image, ImageError, ToneMapping, exr, hdr, pfm};
+use std::fs;
+use std::path::Path;
+
+/// File formats which keep the linear radiance of a film, without clamping it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HdrFormat {
+    /// Radiance RGBE, run-length encoded
+    Radiance,
+    /// Portable Float Map, with 32-bit floats
+    Pfm,
+    /// Uncompressed scanline OpenEXR
+    Exr(ExrPixelType),
+}
+
+impl HdrFormat {
+    /// Pick the format matching the extension of `path`, ignoring case
+    ///
+    /// OpenEXR files are written with half floats
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<HdrFormat> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "hdr" => Some(HdrFormat::Radiance),
+            "pfm" => Some(HdrFormat::Pfm),
+            "exr" => Some(HdrFormat::Exr(ExrPixelType::Half)),
+            _ => None,
+        }
+    }
+}
+
+/// Framebuffer holding the linear radiance rendered for every pixel
+///
+/// Every pixel keeps the sum of its samples and their count, so films rendered separately,
+/// e.g. with different seeds or sample counts, can be combined into one. Pixels are stored row
+/// by row from the top left
+#[derive(Clone)]
+pub struct Film {
+    width: usize,
+    height: usize,
+    sums: Vec<Color>,
+    sample_counts: Vec<u32>,
+}
+
+impl Film {
+    /// Create a black film without any sample
+    pub fn new(width: usize, height: usize) -> Self {
+        Film {
+            width,
+            height,
+            sums: vec![Color::default(); width * height],
+            sample_counts: vec![0; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Add a single sample of the radiance of the pixel in column `x` and row `y`
+    pub fn add_sample(&mut self, x: usize, y: usize, color: Color) {
+        self.add_samples(x, y, color, 1);
+    }
+
+    /// Add `count` samples of the pixel in column `x` and row `y`, whose colors sum to `sum`
+    pub fn add_samples(&mut self, x: usize, y: usize, sum: Color, count: u32) {
+        let index = self.index(x, y);
+        self.sums[index] += sum;
+        self.sample_counts[index] += count;
+    }
+
+    /// Get the average of the samples of the pixel in column `x` and row `y`, black if it has
+    /// none
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        let index = self.index(x, y);
+        match self.sample_counts[index] {
+            0 => Color::default(),
+            count => (1.0 / f64::from(count)) * self.sums[index].clone(),
+        }
+    }
+
+    /// Get the number of samples taken for the pixel in column `x` and row `y`
+    pub fn sample_count(&self, x: usize, y: usize) -> u32 {
+        self.sample_counts[self.index(x, y)]
+    }
+
+    /// Get the average color of every pixel, row by row
+    pub fn pixels(&self) -> impl Iterator<Item = Color> + '_ {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| self.pixel(x, y)))
+    }
+
+    /// Add all the samples of `other` to this film, so every pixel becomes the average of the
+    /// samples of both
+    ///
+    /// Panics if the films don't have the same size
+    pub fn merge(&mut self, other: &Film) {
+        assert_eq!(
+            (self.width, self.height),
+            (other.width, other.height),
+            "Film size mismatch"
+        );
+        for (sum, other_sum) in self.sums.iter_mut().zip(&other.sums) {
+            *sum += other_sum.clone();
+        }
+        for (count, other_count) in self.sample_counts.iter_mut().zip(&other.sample_counts) {
+            *count += other_count;
+        }
+    }
+
+    /// Convert the film to a gamma encoded image which can be written in any format, clamping
+    /// the radiance to [0, 1]
+    pub fn to_image(&self) -> Image {
+        self.to_image_with(&ToneMapping::default())
+    }
+
+    /// Convert the film to a gamma encoded image, compressing the radiance with `tone_mapping`
+    pub fn to_image_with(&self, tone_mapping: &ToneMapping) -> Image {
+        let pixels = self
+            .pixels()
+            .map(|color| tone_mapping.apply(&color).to_gamma())
+            .collect();
+        Image::new(self.width, self.height, pixels)
+    }
+
+    /// Encode the linear radiance of the film in `format`
+    pub fn encode(&self, format: HdrFormat) -> Vec<u8> {
+        match format {
+            HdrFormat::Radiance => hdr::encode_hdr(self),
+            HdrFormat::Pfm => pfm::encode_pfm(self),
+            HdrFormat::Exr(pixel_type) => exr::encode_exr(self, pixel_type),
+        }
+    }
+
+    /// Write the film to the file at `path`, in the format matching its extension
+    ///
+    /// High dynamic range formats keep the linear radiance, while the others get the image
+    /// returned by `to_image`
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ImageError> {
+        let path = path.as_ref();
+        match HdrFormat::from_path(path) {
+            Some(format) => self.save_with_format(path, format),
+            None => self.to_image().save(path),
+        }
+    }
+
+    /// Write the linear radiance of the film to the file at `path` in `format`, whatever its
+    /// extension
+    pub fn save_with_format<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: HdrFormat,
+    ) -> Result<(), ImageError> {
+        let path = path.as_ref();
+        fs::write(path, self.encode(format)).map_err(|source| ImageError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(x < self.width && y < self.height, "Pixel out of the film");
+        y * self.width + x
+    }
+}
+
+#[cfg(test)]
+mod film_tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_averages_all_samples() {
+        let mut a = Film::new(3, 2);
+        a.add_sample(2, 1, Color::new(1.0, 0.0, 0.5));
+        let mut b = Film::new(3, 2);
+        b.add_samples(2, 1, Color::new(0.8, 0.6, 0.0), 3);
+        b.add_sample(0, 0, Color::new(0.25, 0.25, 0.25));
+
+        a.merge(&b);
+        assert_eq!(a.sample_count(2, 1), 4);
+        let pixel = a.pixel(2, 1);
+        assert!((pixel.r() - 0.45).abs() < 1e-12);
+        assert!((pixel.g() - 0.15).abs() < 1e-12);
+        assert!((pixel.b() - 0.125).abs() < 1e-12);
+        assert_eq!(a.sample_count(1, 0), 0);
+        assert_eq!(a.pixel(1, 0).r(), 0.0);
+
+        let image = a.to_image();
+        assert_eq!((image.width(), image.height()), (3, 2));
+        assert!((image.pixel(0, 0).g() - 0.5).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{Color, Film};
+
+// Shortest run of equal bytes worth encoding as a run in the RLE scanlines
+const MIN_RUN_LENGTH: usize = 4;
+
+/// Encode the linear radiance of `film` as a Radiance HDR (RGBE) image
+///
+/// Scanlines are run-length encoded when the width allows it. Negative values are clamped to 0
+pub fn encode_hdr(film: &Film) -> Vec<u8> {
+    let (width, height) = (film.width(), film.height());
+    let mut output =
+        format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {height} +X {width}\n").into_bytes();
+
+    let mut scanline: Vec<[u8; 4]> = Vec::with_capacity(width);
+    for y in 0..height {
+        scanline.clear();
+        scanline.extend((0..width).map(|x| rgbe(&film.pixel(x, y))));
+
+        // Only widths between 8 and 32767 can be run-length encoded
+        if !(8..32768).contains(&width) {
+            output.extend(scanline.iter().flatten());
+            continue;
+        }
+
+        output.extend_from_slice(&[2, 2, (width >> 8) as u8, width as u8]);
+        // Every component is encoded separately
+        for component in 0..4 {
+            let bytes: Vec<u8> = scanline.iter().map(|pixel| pixel[component]).collect();
+            write_runs(&bytes, &mut output);
+        }
+    }
+
+    output
+}
+
+// Shared exponent representation: 3 mantissas and an exponent biased by 128
+fn rgbe(color: &Color) -> [u8; 4] {
+    // NaN components become 0 too
+    let [r, g, b] = [color.r(), color.g(), color.b()].map(|value| value.max(0.0));
+    let max = r.max(g).max(b);
+    if max < 1e-32 {
+        return [0; 4];
+    }
+
+    // max = mantissa * 2^exponent, with mantissa in [0.5, 1)
+    let mut exponent = (max.log2().floor() as i32).min(126) + 1;
+    if max / 2f64.powi(exponent) >= 1.0 {
+        exponent += 1;
+    }
+    let exponent = exponent.min(127);
+    let scale = 256.0 / 2f64.powi(exponent);
+    [
+        (r * scale).min(255.0) as u8,
+        (g * scale).min(255.0) as u8,
+        (b * scale).min(255.0) as u8,
+        (exponent + 128) as u8,
+    ]
+}
+
+// Write `bytes` as runs of a repeated byte, with a count above 128, and literal sequences, with
+// a count up to 128
+fn write_runs(bytes: &[u8], output: &mut Vec<u8>) {
+    let mut position = 0;
+    while position < bytes.len() {
+        // Find the next run long enough to be encoded as such
+        let mut run_start = position;
+        let mut run_length = 0;
+        while run_start < bytes.len() {
+            run_length = bytes[run_start..]
+                .iter()
+                .take(127)
+                .take_while(|&&byte| byte == bytes[run_start])
+                .count();
+            if run_length >= MIN_RUN_LENGTH {
+                break;
+            }
+            run_start += run_length;
+        }
+        if run_length < MIN_RUN_LENGTH {
+            run_start = bytes.len();
+        }
+
+        // The bytes before the run are written as literals
+        for literal in bytes[position..run_start].chunks(128) {
+            output.push(literal.len() as u8);
+            output.extend_from_slice(literal);
+        }
+        if run_start < bytes.len() {
+            output.extend_from_slice(&[128 + run_length as u8, bytes[run_start]]);
+        }
+        position = run_start + run_length;
+    }
+}
+
+#[cfg(test)]
+mod hdr_tests {
+    use super::*;
+
+    #[test]
+    fn test_rgbe_and_runs() {
+        // 2^3 * (0.5, 0.25, 0)
+        assert_eq!(rgbe(&Color::new(4.0, 2.0, -1.0)), [128, 64, 0, 131]);
+        assert_eq!(rgbe(&Color::new(0.75, 0.0, 0.0)), [192, 0, 0, 128]);
+        assert_eq!(rgbe(&Color::default()), [0; 4]);
+
+        let mut output = Vec::new();
+        write_runs(&[1, 2, 3, 3, 3, 3, 3, 4], &mut output);
+        assert_eq!(output, [2, 1, 2, 133, 3, 1, 4]);
+    }
+}
@@ -1,20 +1,45 @@
 use crate::{
-    Interval, Point3, Ray, Vec3,
+    Aabb, Interval, Point3, Ray, Sampler, Vec3,
     material::{self, Material},
 };
-use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct HitRecord {
-    pub p: Point3,             // The point of intersection
-    pub normal: Vec3,          // The normal to p (can be inwards or outwards to the object)
-    pub mat: Rc<dyn Material>, // Store material of the hit; used later to generate the scatterd ray
-    pub t: f64,                // The time of intersection, the `t` variable in ray equation
-    pub front_face: bool,      // Whether the ray hit the object from outside the surface on inside
+    pub p: Point3,              // The point of intersection
+    pub normal: Vec3,           // The normal to p (can be inwards or outwards to the object)
+    pub mat: Arc<dyn Material>, // Store material of the hit; used later to generate the scatterd ray
+    pub t: f64,                 // The time of intersection, the `t` variable in ray equation
+    pub u: f64,                 // Horizontal surface coordinate of p, between 0 and 1
+    pub v: f64,                 // Vertical surface coordinate of p, between 0 and 1
+    pub barycentric: [f64; 3],  // Weights of the vertices of a hit triangle which give p
+    pub front_face: bool,       // Whether the ray hit the object from outside the surface on inside
 }
 
-pub trait Hittable {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool;
+/// Object which a ray can hit
+///
+/// The same world is read by all the render threads, hence the `Send + Sync` bound
+pub trait Hittable: Send + Sync {
+    /// Store the closest hit of `r` within `ray_t` in `rec` and return true, or return false
+    ///
+    /// Objects which need random numbers, like participating media, draw them from `sampler`
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord, sampler: &mut dyn Sampler)
+    -> bool;
+
+    /// Axis-aligned box enclosing the whole object
+    fn bounding_box(&self) -> Aabb;
+
+    /// Density, with respect to solid angle, of `random` generating `direction` from `origin`
+    ///
+    /// Only objects which can be sampled as lights implement it; the others have a zero density
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    /// Generate a random direction from `origin` towards the object
+    fn random(&self, _origin: &Point3, _sampler: &mut dyn Sampler) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
 }
 
 impl HitRecord {
@@ -37,8 +62,11 @@ impl Default for HitRecord {
         HitRecord {
             p: Default::default(),
             normal: Default::default(),
-            mat: Rc::new(material::Metal::default()),
+            mat: Arc::new(material::Metal::default()),
             t: Default::default(),
+            u: Default::default(),
+            v: Default::default(),
+            barycentric: Default::default(),
             front_face: Default::default(),
         }
     }
@@ -1,31 +1,52 @@
-use crate::{HitRecord, Hittable, Interval, Ray};
-use std::rc::Rc;
+use crate::{Aabb, HitRecord, Hittable, Interval, Point3, Ray, Sampler, Vec3};
+use std::sync::Arc;
 
 #[derive(Default)]
 pub struct HittableList {
-    objects: Vec<Rc<dyn Hittable>>,
+    pub(crate) objects: Vec<Arc<dyn Hittable>>,
+    bbox: Aabb,
 }
 
 impl HittableList {
-    pub fn add(&mut self, object: Rc<dyn Hittable>) {
+    /// Create a list containing a single object
+    pub fn new(object: Arc<dyn Hittable>) -> Self {
+        let mut list: HittableList = Default::default();
+        list.add(object);
+        list
+    }
+
+    pub fn add(&mut self, object: Arc<dyn Hittable>) {
+        self.bbox = Aabb::surrounding(&self.bbox, &object.bounding_box());
         self.objects.push(object.clone());
     }
 
     pub fn clear(&mut self) {
         self.objects.clear();
+        self.bbox = Aabb::empty();
     }
 }
 
 impl Hittable for HittableList {
     /// Find closest intersection from the list of hittable objects
     /// and update the information in the given `HitRecord` variable
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+    fn hit(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
         let mut temp_rec: HitRecord = Default::default();
         let mut hit_anything = false;
         let mut closest_so_far = ray_t.max;
 
         for object in self.objects.iter() {
-            if object.hit(r, Interval::new(ray_t.min, closest_so_far), &mut temp_rec) {
+            if object.hit(
+                r,
+                Interval::new(ray_t.min, closest_so_far),
+                &mut temp_rec,
+                sampler,
+            ) {
                 hit_anything = true;
                 closest_so_far = temp_rec.t;
                 *rec = temp_rec.clone();
@@ -34,4 +55,28 @@ impl Hittable for HittableList {
 
         hit_anything
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox.clone()
+    }
+
+    /// Average density of the objects, as `random` picks one of them uniformly
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let weight = 1.0 / self.objects.len() as f64;
+        self.objects
+            .iter()
+            .map(|object| weight * object.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let index = (sampler.get_1d() * self.objects.len() as f64) as usize;
+        self.objects[index.min(self.objects.len() - 1)].random(origin, sampler)
+    }
 }
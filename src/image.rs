@@ -0,0 +1,173 @@
+use crate::{Color, png, ppm};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Errors returned while reading or writing images
+#[derive(Debug)]
+pub enum ImageError {
+    /// The file couldn't be read or written
+    Io { path: PathBuf, source: io::Error },
+    /// The data isn't a valid image in a supported format
+    Decode(String),
+    /// The file extension doesn't match any supported format
+    UnknownFormat(PathBuf),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ImageError::Decode(message) => write!(f, "invalid image: {message}"),
+            ImageError::UnknownFormat(path) => {
+                write!(f, "{}: unknown image format", path.display())
+            }
+        }
+    }
+}
+
+impl Error for ImageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ImageError::Io { source, .. } => Some(source),
+            ImageError::Decode(_) | ImageError::UnknownFormat(_) => None,
+        }
+    }
+}
+
+/// Number of bits used to store every color channel
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BitDepth {
+    #[default]
+    Eight,
+    Sixteen,
+}
+
+/// File formats which images can be written to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Plain (P3) PPM, with 8 bits per channel
+    PlainPpm,
+    /// Raw (P6) PPM
+    Ppm(BitDepth),
+    Png(BitDepth),
+}
+
+impl ImageFormat {
+    /// Pick the format matching the extension of `path`, ignoring case
+    ///
+    /// PPM and PNG files are written with 8 bits per channel
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<ImageFormat> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(ImageFormat::Ppm(BitDepth::Eight)),
+            "png" => Some(ImageFormat::Png(BitDepth::Eight)),
+            _ => None,
+        }
+    }
+}
+
+/// Bitmap stored row by row from the top left pixel
+///
+/// The color components are the values stored in the file scaled to [0, 1], so they are
+/// usually gamma encoded
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Image {
+    /// Create an image from its `pixels` in row major order
+    ///
+    /// Panics if there isn't exactly one pixel for every position
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), width * height, "Image size mismatch");
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Read a PNG, PPM or PGM image from the file at `path`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Image, ImageError> {
+        let path = path.as_ref();
+        let data = fs::read(path).map_err(|source| ImageError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Image::decode(&data)
+    }
+
+    /// Decode a PNG, PPM or PGM image, detecting the format from the first bytes of `data`
+    pub fn decode(data: &[u8]) -> Result<Image, ImageError> {
+        if data.starts_with(&png::SIGNATURE) {
+            png::decode_png(data)
+        } else if data.starts_with(b"P") {
+            ppm::decode_ppm(data)
+        } else {
+            Err(ImageError::Decode("unknown image format".to_string()))
+        }
+    }
+
+    /// Encode the image in `format`
+    ///
+    /// The pixels are stored as they are, clamped to [0, 1]
+    pub fn encode(&self, format: ImageFormat) -> Vec<u8> {
+        match format {
+            ImageFormat::PlainPpm => ppm::encode_plain_ppm(self),
+            ImageFormat::Ppm(bit_depth) => ppm::encode_ppm(self, bit_depth),
+            ImageFormat::Png(bit_depth) => png::encode_png(self, bit_depth),
+        }
+    }
+
+    /// Write the image to the file at `path`, in the format matching its extension
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ImageError> {
+        let path = path.as_ref();
+        let format =
+            ImageFormat::from_path(path).ok_or_else(|| ImageError::UnknownFormat(path.into()))?;
+        self.save_with_format(path, format)
+    }
+
+    /// Write the image to the file at `path` in `format`, whatever its extension
+    pub fn save_with_format<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: ImageFormat,
+    ) -> Result<(), ImageError> {
+        let path = path.as_ref();
+        fs::write(path, self.encode(format)).map_err(|source| ImageError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the color of the pixel in column `x` and row `y`
+    pub fn pixel(&self, x: usize, y: usize) -> &Color {
+        &self.pixels[y * self.width + x]
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+}
+
+// Scale `value` from [0, 1] to an integer sample between 0 and `max_value`
+//
+// Every sample covers an interval of the same width, as `Color::write_color` does for bytes
+pub(crate) fn quantize(value: f64, max_value: u16) -> u16 {
+    // NaN values become 0 like in `Color::write_color`
+    let sample = ((f64::from(max_value) + 1.0) * value.clamp(0.0, 1.0)) as u32;
+    sample.min(u32::from(max_value)) as u16
+}
@@ -1,4 +1,5 @@
 /// Manage real-valued intervals with a minimum and maximum
+#[derive(Clone)]
 pub struct Interval {
     pub min: f64,
     pub max: f64,
@@ -9,6 +10,11 @@ impl Interval {
         Interval { min, max }
     }
 
+    /// Create the tightest interval enclosing both the intervals
+    pub fn enclosing(a: &Interval, b: &Interval) -> Self {
+        Interval::new(a.min.min(b.min), a.max.max(b.max))
+    }
+
     pub fn size(&self) -> f64 {
         self.max - self.min
     }
@@ -29,6 +35,12 @@ impl Interval {
         Self::new(f64::MIN, f64::MAX)
     }
 
+    /// Pad the interval by `delta / 2` on both sides
+    pub fn expand(&self, delta: f64) -> Self {
+        let padding = delta / 2.0;
+        Interval::new(self.min - padding, self.max + padding)
+    }
+
     /// Clamp the value of `x` within the interval
     pub fn clamp(&self, x: f64) -> f64 {
         x.clamp(self.min, self.max)
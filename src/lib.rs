@@ -1,22 +1,74 @@
+mod aabb;
+mod bvh;
 mod camera;
 mod color;
+mod constant_medium;
+mod exr;
+mod film;
+mod hdr;
 mod hittable;
 mod hittable_list;
+mod image;
 mod interval;
+mod mat4;
 mod material;
+mod obj;
+mod onb;
+mod pdf;
+mod perlin;
+mod pfm;
+mod png;
 mod point;
+mod ppm;
+mod quad;
+mod quat;
 mod ray;
+mod sah_bvh;
+mod sampler;
 mod sphere;
+mod texture;
+mod tone_map;
+mod transform;
+mod triangle;
 pub mod utils;
 mod vec;
+mod volume;
+mod zlib;
 
-pub use camera::Camera;
+pub use aabb::Aabb;
+pub use bvh::BvhNode;
+pub use camera::{Background, Camera};
 pub use color::Color;
+pub use constant_medium::ConstantMedium;
+pub use exr::ExrPixelType;
+pub use film::{Film, HdrFormat};
 pub use hittable::{HitRecord, Hittable};
 pub use hittable_list::HittableList;
+pub use image::{BitDepth, Image, ImageError, ImageFormat};
 pub use interval::Interval;
-pub use material::{Dielectric, Lambertian, Material, Metal};
+pub use mat4::Mat4;
+pub use material::{
+    Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal, ScatterRecord,
+};
+pub use obj::{ObjError, load_mtl, load_obj};
+pub use onb::Onb;
+pub use pdf::{CosinePdf, HittablePdf, MixturePdf, Pdf, SpherePdf};
+pub use perlin::Perlin;
 pub use point::Point3;
+pub use quad::{Quad, make_box};
+pub use quat::Quat;
 pub use ray::Ray;
+pub use sah_bvh::{BvhStats, SahBvh};
+pub use sampler::{
+    HaltonSampler, IndependentSampler, Sampler, SamplerType, SobolSampler, StratifiedSampler,
+};
 pub use sphere::Sphere;
+pub use texture::{
+    CheckerTexture, CloudTexture, ImageTexture, MarbleTexture, SolidColor, Texture, TextureFilter,
+    UvCheckerTexture, WoodTexture, WrapMode,
+};
+pub use tone_map::{ToneMapOperator, ToneMapping};
+pub use transform::Transform;
+pub use triangle::{Triangle, TriangleMesh};
 pub use vec::Vec3;
+pub use volume::{DensityField, HeterogeneousMedium, NoiseDensity, VoxelGrid};
@@ -0,0 +1,248 @@
+use crate::{Point3, Vec3, utils::degrees_to_radians};
+use std::ops::Mul;
+
+/// 4x4 matrix of an affine transformation, acting on column vectors
+///
+/// Points are transformed with the translation and vectors without it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mat4 {
+    m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    /// Create a matrix from its rows
+    pub fn new(rows: [[f64; 4]; 4]) -> Self {
+        Mat4 { m: rows }
+    }
+
+    pub fn identity() -> Self {
+        Mat4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn translation(offset: &Vec3) -> Self {
+        Mat4::new([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Scale by a different factor along every axis
+    pub fn scaling(factors: &Vec3) -> Self {
+        Mat4::new([
+            [factors.x(), 0.0, 0.0, 0.0],
+            [0.0, factors.y(), 0.0, 0.0],
+            [0.0, 0.0, factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Rotate counterclockwise by `degrees` around `axis`, looking from its tip towards the
+    /// origin
+    pub fn rotation(axis: &Vec3, degrees: f64) -> Self {
+        let a = axis.unit_vector();
+        let (sin, cos) = degrees_to_radians(degrees).sin_cos();
+        let t = 1.0 - cos;
+        // Rodrigues' rotation formula
+        Mat4::new([
+            [
+                t * a.x() * a.x() + cos,
+                t * a.x() * a.y() - sin * a.z(),
+                t * a.x() * a.z() + sin * a.y(),
+                0.0,
+            ],
+            [
+                t * a.x() * a.y() + sin * a.z(),
+                t * a.y() * a.y() + cos,
+                t * a.y() * a.z() - sin * a.x(),
+                0.0,
+            ],
+            [
+                t * a.x() * a.z() - sin * a.y(),
+                t * a.y() * a.z() + sin * a.x(),
+                t * a.z() * a.z() + cos,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Get the element in `row` and `column`
+    pub fn get(&self, row: usize, column: usize) -> f64 {
+        self.m[row][column]
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, element) in row.iter_mut().enumerate() {
+                *element = self.m[j][i];
+            }
+        }
+        Mat4::new(m)
+    }
+
+    /// Invert the matrix with Gauss-Jordan elimination, returning `None` if it is singular
+    ///
+    /// Pivots are compared to the size of the matrix, so the result doesn't depend on its scale
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inverse = Mat4::identity().m;
+        // Infinity norm, the largest sum of the absolute values of a row
+        let norm = self
+            .m
+            .iter()
+            .map(|row| row.iter().map(|x| x.abs()).sum::<f64>())
+            .fold(0.0, f64::max);
+        // Pivots below this are rounding noise left by the elimination of a singular matrix
+        let tolerance = 1e-12 * norm;
+
+        for column in 0..4 {
+            // Use the largest pivot available to limit rounding errors
+            let pivot = (column..4)
+                .max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))
+                .unwrap();
+            if a[pivot][column].abs() <= tolerance {
+                return None;
+            }
+            a.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = 1.0 / a[column][column];
+            for k in 0..4 {
+                a[column][k] *= scale;
+                inverse[column][k] *= scale;
+            }
+            for row in 0..4 {
+                if row != column {
+                    let factor = a[row][column];
+                    for k in 0..4 {
+                        a[row][k] -= factor * a[column][k];
+                        inverse[row][k] -= factor * inverse[column][k];
+                    }
+                }
+            }
+        }
+
+        Some(Mat4::new(inverse))
+    }
+
+    /// Apply the transformation to a point, including the translation
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        let m = &self.m;
+        Point3::new(
+            m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3],
+            m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3],
+            m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3],
+        )
+    }
+
+    /// Apply the transformation to a direction, ignoring the translation
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::identity()
+    }
+}
+
+/// Compose two transformations: `a * b` applies `b` first, then `a`
+impl Mul<&Mat4> for &Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: &Mat4) -> Self::Output {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, element) in row.iter_mut().enumerate() {
+                *element = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Mat4::new(m)
+    }
+}
+
+impl Mul<Mat4> for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Self::Output {
+        &self * &other
+    }
+}
+
+#[cfg(test)]
+mod mat4_tests {
+    use super::*;
+
+    fn assert_matrix_close(a: &Mat4, b: &Mat4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((a.get(i, j) - b.get(i, j)).abs() < 1e-9, "{a:?} != {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_identities() {
+        let m = Mat4::translation(&Vec3::new(1.0, -2.0, 3.0))
+            * Mat4::rotation(&Vec3::new(0.2, 1.0, -0.4), 33.0)
+            * Mat4::scaling(&Vec3::new(2.0, 0.5, 3.0));
+        let inverse = m.inverse().unwrap();
+
+        assert_matrix_close(&(&m * &inverse), &Mat4::identity());
+        assert_matrix_close(&(&inverse * &m), &Mat4::identity());
+        assert_matrix_close(&m.transpose().transpose(), &m);
+        // (AB)^T = B^T A^T
+        assert_matrix_close(
+            &(&m * &inverse.transpose()).transpose(),
+            &(&inverse * &m.transpose()),
+        );
+
+        // The inverse of a rotation is its transpose
+        let r = Mat4::rotation(&Vec3::new(1.0, 1.0, 0.0), 50.0);
+        assert_matrix_close(&r.inverse().unwrap(), &r.transpose());
+
+        // Composition applies the right matrix first
+        let p = Point3::new(1.0, 0.0, 0.0);
+        let m = Mat4::translation(&Vec3::new(0.0, 0.0, 5.0))
+            * Mat4::rotation(&Vec3::new(0.0, 0.0, 1.0), 90.0);
+        let q = m.transform_point(&p);
+        assert!((q.x().abs() + (q.y() - 1.0).abs() + (q.z() - 5.0).abs()) < 1e-9);
+
+        assert!(Mat4::scaling(&Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn test_inverse_tolerance_is_relative() {
+        let rows = [
+            [2.0, 0.3, -1.0, 0.5],
+            [0.1, 1.5, 0.7, -2.0],
+            [-0.4, 0.2, 3.0, 1.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        for scale in [1e-14, 1.0, 1e14] {
+            let m = Mat4::new(rows.map(|row| row.map(|x| scale * x)));
+            let inverse = m.inverse().unwrap();
+            assert_matrix_close(&(&m * &inverse), &Mat4::identity());
+
+            // The third row is a combination of the first two, up to rounding
+            let mut singular = rows.map(|row| row.map(|x| scale * x));
+            singular[2] = [0, 1, 2, 3].map(|j| 0.3 * singular[0][j] - 0.7 * singular[1][j]);
+            assert!(Mat4::new(singular).inverse().is_none());
+        }
+        assert!(Mat4::new([[0.0; 4]; 4]).inverse().is_none());
+    }
+}
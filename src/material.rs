@@ -1,27 +1,52 @@
-use crate::{Color, HitRecord, Ray, Vec3, utils::random_f64};
+use crate::{Color, CosinePdf, HitRecord, Pdf, Ray, Sampler, SolidColor, SpherePdf, Texture, Vec3};
+use std::f64::consts::PI;
+use std::sync::Arc;
 
-pub trait Material {
+/// Outcome of a ray scattering off a material
+#[derive(Default)]
+pub struct ScatterRecord {
+    /// Fraction of the light kept by the scattering, for each color component
+    pub attenuation: Color,
+    /// Density to pick the scattered direction from; `None` for specular materials, which
+    /// scatter along `skip_pdf_ray` instead
+    pub pdf: Option<Box<dyn Pdf>>,
+    /// Scattered ray of specular materials, whose direction doesn't follow a density
+    pub skip_pdf_ray: Ray,
+}
+
+/// Describes how a ray interacts with a surface
+pub trait Material: Send + Sync {
+    /// Fill `srec` and return true if the ray `r_in` scatters at the hit, false if it's absorbed
     fn scatter(
         &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+        _srec: &mut ScatterRecord,
+        _sampler: &mut dyn Sampler,
     ) -> bool {
         false
     }
+
+    /// Density of the material scattering `r_in` into the direction of `scattered`, with respect
+    /// to solid angle
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+
+    /// Light given off by the material at the hit point, black unless the material is a light
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
 }
 
 /// Material which scatters and attenuates light accoording to it reflectance
-#[derive(Default)]
 pub struct Lambertian {
-    albedo: Color,
+    tex: Arc<dyn Texture>,
 }
 
 /// Material which completely reflects incident ray and attenuate light accoording to it reflectance
-#[derive(Default)]
 pub struct Metal {
-    albedo: Color,
+    tex: Arc<dyn Texture>,
     fuzz: f64, // A value between 0 and 1, indicating fuzzing magnitude
 }
 
@@ -31,55 +56,88 @@ pub struct Dielectric {
     refraction_index: f64, // Refractive index in vacuum or air, or the ratio of the material's refractive index over the refractive index of the enclosing medium
 }
 
+/// Material which emits light and doesn't scatter any
+pub struct DiffuseLight {
+    tex: Arc<dyn Texture>,
+}
+
+/// Phase function of a participating medium scattering light equally in every direction
+pub struct Isotropic {
+    tex: Arc<dyn Texture>,
+}
+
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
-        Lambertian { albedo }
+        Lambertian::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    /// Create the material with the reflectance given by the texture `tex`
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Lambertian { tex }
+    }
+}
+
+impl Default for Lambertian {
+    fn default() -> Self {
+        Lambertian::new(Color::default())
     }
 }
 
 impl Material for Lambertian {
     fn scatter(
         &self,
-        r_in: &Ray,
+        _r_in: &Ray,
         rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
+        srec: &mut ScatterRecord,
+        _sampler: &mut dyn Sampler,
     ) -> bool {
-        let mut scatter_direction = rec.normal.clone() + Vec3::random_unit_vector();
-        // Catch degenerate scatter direction
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal.clone();
-        }
-        *scattered = Ray::new(rec.p.clone(), scatter_direction);
-        *attenuation = self.albedo.clone();
+        srec.attenuation = self.tex.value(rec.u, rec.v, &rec.p);
+        srec.pdf = Some(Box::new(CosinePdf::new(&rec.normal)));
         true
     }
+
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_theta = rec.normal.dot(&scattered.direction().unit_vector());
+        f64::max(0.0, cos_theta / PI)
+    }
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: f64) -> Self {
+        Metal::from_texture(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    /// Create the material with the reflectance given by the texture `tex`
+    pub fn from_texture(tex: Arc<dyn Texture>, fuzz: f64) -> Self {
         Metal {
-            albedo,
+            tex,
             fuzz: fuzz.clamp(0.0, 1.0),
         }
     }
 }
 
+impl Default for Metal {
+    fn default() -> Self {
+        Metal::new(Color::default(), 0.0)
+    }
+}
+
 impl Material for Metal {
     fn scatter(
         &self,
         r_in: &Ray,
         rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
+        srec: &mut ScatterRecord,
+        sampler: &mut dyn Sampler,
     ) -> bool {
         let mut reflected = r_in.direction().reflect(&rec.normal);
         // Fuzz the reflected ray in a random direction
-        reflected = reflected.unit_vector() + self.fuzz * Vec3::random_unit_vector();
-        *scattered = Ray::new(rec.p.clone(), reflected);
-        *attenuation = self.albedo.clone();
+        reflected = reflected.unit_vector() + self.fuzz * Vec3::random_unit_vector(sampler);
+        srec.skip_pdf_ray = Ray::with_time(rec.p.clone(), reflected, r_in.time());
+        srec.attenuation = self.tex.value(rec.u, rec.v, &rec.p);
+        srec.pdf = None;
         // If the ray is below the surface then, absorb the ray in the surface
-        scattered.direction().dot(&rec.normal) > 0.0
+        srec.skip_pdf_ray.direction().dot(&rec.normal) > 0.0
     }
 }
 
@@ -93,10 +151,11 @@ impl Material for Dielectric {
         &self,
         r_in: &Ray,
         rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
+        srec: &mut ScatterRecord,
+        sampler: &mut dyn Sampler,
     ) -> bool {
-        *attenuation = Color::new(1.0, 1.0, 1.0);
+        srec.attenuation = Color::new(1.0, 1.0, 1.0);
+        srec.pdf = None;
         // Refraction formula takes refraction index of incident medium in the numerator
         // So, if the incident ray comes from another medium, take inverse of the self.refraction_index
         let ri = if rec.front_face {
@@ -112,15 +171,14 @@ impl Material for Dielectric {
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let direction = {
-            if ri * sin_theta > 1.0 || Dielectric::reflectance(cos_theta, ri) > random_f64(0.0, 1.0)
-            {
+            if ri * sin_theta > 1.0 || Dielectric::reflectance(cos_theta, ri) > sampler.get_1d() {
                 unit_direction.reflect(&rec.normal)
             } else {
                 unit_direction.refract(&rec.normal, ri)
             }
         };
 
-        *scattered = Ray::new(rec.p.clone(), direction);
+        srec.skip_pdf_ray = Ray::with_time(rec.p.clone(), direction, r_in.time());
         true
     }
 }
@@ -132,3 +190,142 @@ impl Dielectric {
         r0 + (1.0 - r0) * ((1.0 - cosine).powi(5))
     }
 }
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        DiffuseLight::from_texture(Arc::new(SolidColor::new(emit)))
+    }
+
+    /// Create the light with the emitted color given by the texture `tex`
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        DiffuseLight { tex }
+    }
+}
+
+impl Default for DiffuseLight {
+    fn default() -> Self {
+        DiffuseLight::new(Color::default())
+    }
+}
+
+impl Material for DiffuseLight {
+    /// Lights only shine from the side their normal points to
+    fn emitted(&self, _r_in: &Ray, rec: &HitRecord) -> Color {
+        if !rec.front_face {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        self.tex.value(rec.u, rec.v, &rec.p)
+    }
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Self {
+        Isotropic::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    /// Create the phase function with the albedo given by the texture `tex`
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Isotropic { tex }
+    }
+}
+
+impl Default for Isotropic {
+    fn default() -> Self {
+        Isotropic::new(Color::default())
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        rec: &HitRecord,
+        srec: &mut ScatterRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> bool {
+        srec.attenuation = self.tex.value(rec.u, rec.v, &rec.p);
+        srec.pdf = Some(Box::new(SpherePdf));
+        true
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+#[cfg(test)]
+mod material_tests {
+    use super::*;
+    use crate::{CheckerTexture, IndependentSampler, Point3, UvCheckerTexture};
+
+    #[test]
+    fn test_textured_attenuation() {
+        let mut sampler = IndependentSampler::new(0);
+        let textures: [Arc<dyn Texture>; 2] = [
+            Arc::new(CheckerTexture::from_colors(
+                1.0,
+                Color::new(0.9, 0.8, 0.7),
+                Color::new(0.1, 0.2, 0.3),
+            )),
+            Arc::new(UvCheckerTexture::from_colors(
+                2,
+                2,
+                Color::new(0.9, 0.8, 0.7),
+                Color::new(0.1, 0.2, 0.3),
+            )),
+        ];
+        let materials = |tex: &Arc<dyn Texture>| -> [Arc<dyn Material>; 2] {
+            [
+                Arc::new(Lambertian::from_texture(Arc::clone(tex))),
+                Arc::new(Metal::from_texture(Arc::clone(tex), 0.0)),
+            ]
+        };
+
+        let r_in = Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        for tex in &textures {
+            for mat in materials(tex) {
+                // Points in an even and an odd cell of both textures
+                for (p, u, v) in [
+                    (Point3::new(0.5, 0.0, 0.5), 0.25, 0.25),
+                    (Point3::new(1.5, 0.0, 0.5), 0.75, 0.25),
+                ] {
+                    let rec = HitRecord {
+                        p: p.clone(),
+                        normal: Vec3::new(0.0, 1.0, 0.0),
+                        u,
+                        v,
+                        front_face: true,
+                        ..Default::default()
+                    };
+                    let mut srec = ScatterRecord::default();
+                    assert!(mat.scatter(&r_in, &rec, &mut srec, &mut sampler));
+                    let expected = tex.value(u, v, &p);
+                    assert_eq!(srec.attenuation.r(), expected.r());
+                    assert_eq!(srec.attenuation.g(), expected.g());
+                    assert_eq!(srec.attenuation.b(), expected.b());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_diffuse_light_emits_from_the_front_only() {
+        let mut sampler = IndependentSampler::new(0);
+        let light = DiffuseLight::new(Color::new(4.0, 3.0, 2.0));
+        let r_in = Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+
+        for front_face in [true, false] {
+            let rec = HitRecord {
+                normal: Vec3::new(0.0, 1.0, 0.0),
+                front_face,
+                ..Default::default()
+            };
+            let emitted = light.emitted(&r_in, &rec);
+            let expected = if front_face { 4.0 } else { 0.0 };
+            assert_eq!(emitted.r(), expected);
+
+            let mut srec = ScatterRecord::default();
+            assert!(!light.scatter(&r_in, &rec, &mut srec, &mut sampler));
+        }
+    }
+}
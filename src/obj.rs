@@ -0,0 +1,543 @@
+use crate::{
+    Color, Dielectric, HittableList, Lambertian, Material, Metal, Point3, TriangleMesh, Vec3,
+};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Errors returned while loading OBJ and MTL files
+#[derive(Debug)]
+pub enum ObjError {
+    /// The file couldn't be read
+    Io { path: PathBuf, source: io::Error },
+    /// The line `line` (starting at 1) of the file isn't valid
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+/// Load the Wavefront OBJ model at `path` along with its MTL material libraries
+///
+/// Faces with more than 3 vertices are triangulated as a fan, and negative indices count back
+/// from the last element defined so far. Vertex colors following the position are ignored.
+/// The faces of every material are gathered in their own `TriangleMesh`. Faces without a known
+/// material use `default_material`, which includes the materials of libraries that don't exist,
+/// as models are often shared without them.
+pub fn load_obj<P: AsRef<Path>>(
+    path: P,
+    default_material: Arc<dyn Material>,
+) -> Result<HittableList, ObjError> {
+    let path = path.as_ref();
+    let source = read_file(path)?;
+    let parse_error = |line: usize, message: String| ObjError::Parse {
+        path: path.to_path_buf(),
+        line,
+        message,
+    };
+
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<(f64, f64)> = Vec::new();
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+
+    // Faces of every material in the order the materials were first used
+    let mut groups: Vec<FaceGroup> = Vec::new();
+    let mut group_index: HashMap<String, usize> = HashMap::new();
+    let mut current_material = String::new();
+
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let mut tokens = statement_tokens(line);
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                // Up to x y z w r g b
+                let [x, y, z] =
+                    parse_floats::<3>(&args, 3, 7).map_err(|m| parse_error(line_number, m))?;
+                positions.push(Point3::new(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] =
+                    parse_floats::<3>(&args, 3, 3).map_err(|m| parse_error(line_number, m))?;
+                normals.push(Vec3::new(x, y, z));
+            }
+            "vt" => {
+                // The second and third texture coordinates are optional
+                let [u, v] =
+                    parse_floats::<2>(&args, 1, 3).map_err(|m| parse_error(line_number, m))?;
+                uvs.push((u, v));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(parse_error(
+                        line_number,
+                        format!("face needs atleast 3 vertices, found {}", args.len()),
+                    ));
+                }
+                let face = args
+                    .iter()
+                    .map(|arg| parse_face_vertex(arg, positions.len(), uvs.len(), normals.len()))
+                    .collect::<Result<Vec<FaceVertex>, String>>()
+                    .map_err(|m| parse_error(line_number, m))?;
+
+                let index = *group_index
+                    .entry(current_material.clone())
+                    .or_insert_with(|| {
+                        groups.push(FaceGroup::new(current_material.clone()));
+                        groups.len() - 1
+                    });
+                groups[index].add_face(&face);
+            }
+            "usemtl" => {
+                current_material = args.join(" ");
+            }
+            "mtllib" => {
+                if args.is_empty() {
+                    return Err(parse_error(
+                        line_number,
+                        "missing MTL file name".to_string(),
+                    ));
+                }
+                let directory = path.parent().unwrap_or(Path::new(""));
+                for file_name in args {
+                    match load_mtl(directory.join(file_name)) {
+                        Ok(library) => materials.extend(library),
+                        // Faces of the missing materials fall back to the default one
+                        Err(ObjError::Io { source, .. })
+                            if source.kind() == io::ErrorKind::NotFound => {}
+                        Err(ObjError::Io { source, .. }) => {
+                            return Err(parse_error(
+                                line_number,
+                                format!("can't read MTL file `{file_name}`: {source}"),
+                            ));
+                        }
+                        Err(error) => return Err(error),
+                    }
+                }
+            }
+            // Groups, objects, smoothing groups, lines and points don't affect the triangles
+            _ => {}
+        }
+    }
+
+    let mut list: HittableList = Default::default();
+    for group in groups {
+        let mat = materials
+            .get(&group.material)
+            .cloned()
+            .unwrap_or_else(|| default_material.clone());
+        list.add(Arc::new(group.into_mesh(&positions, &normals, &uvs, mat)));
+    }
+    Ok(list)
+}
+
+/// Load the materials of the MTL library at `path`, keyed by their names
+///
+/// The parameters are mapped onto the closest available material-
+/// - transparent materials (`d` below 1, `Tr` above 0, or a refracting `illum` model)
+///   become `Dielectric` with the index of refraction `Ni`
+/// - materials with a mirror `illum` model, or a specular color `Ks` brighter than the diffuse
+///   color `Kd`, become `Metal` with color `Ks` and fuzz derived from the exponent `Ns`
+/// - everything else becomes `Lambertian` with color `Kd`
+pub fn load_mtl<P: AsRef<Path>>(path: P) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    let path = path.as_ref();
+    let source = read_file(path)?;
+    let parse_error = |line: usize, message: String| ObjError::Parse {
+        path: path.to_path_buf(),
+        line,
+        message,
+    };
+
+    let mut materials = HashMap::new();
+    let mut current: Option<MtlParameters> = None;
+
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let mut tokens = statement_tokens(line);
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if let Some(parameters) = current.take() {
+                materials.insert(parameters.name.clone(), parameters.to_material());
+            }
+            if args.is_empty() {
+                return Err(parse_error(
+                    line_number,
+                    "missing material name".to_string(),
+                ));
+            }
+            current = Some(MtlParameters::new(args.join(" ")));
+            continue;
+        }
+
+        let Some(parameters) = current.as_mut() else {
+            return Err(parse_error(
+                line_number,
+                format!("`{keyword}` appears before any `newmtl`"),
+            ));
+        };
+        let single = |args: &[&str]| {
+            parse_floats::<1>(args, 1, 1)
+                .map(|[value]| value)
+                .map_err(|m| parse_error(line_number, m))
+        };
+        match keyword {
+            "Kd" => parameters.kd = parse_color(&args).map_err(|m| parse_error(line_number, m))?,
+            "Ks" => parameters.ks = parse_color(&args).map_err(|m| parse_error(line_number, m))?,
+            "Ns" => parameters.ns = single(&args)?,
+            "Ni" => parameters.ni = single(&args)?,
+            "d" => parameters.dissolve = single(&args)?,
+            "Tr" => parameters.dissolve = 1.0 - single(&args)?,
+            "illum" => parameters.illum = single(&args)? as u32,
+            // Texture maps, emission and the other parameters have no equivalent
+            _ => {}
+        }
+    }
+
+    if let Some(parameters) = current.take() {
+        materials.insert(parameters.name.clone(), parameters.to_material());
+    }
+    Ok(materials)
+}
+
+fn read_file(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+// Split a line into whitespace separated tokens, dropping the comment
+fn statement_tokens(line: &str) -> std::str::SplitWhitespace<'_> {
+    let statement = match line.find('#') {
+        Some(comment_start) => &line[..comment_start],
+        None => line,
+    };
+    statement.split_whitespace()
+}
+
+// Parse between `min` and `max` numbers, keeping the first `N` of them (missing ones are 0)
+fn parse_floats<const N: usize>(args: &[&str], min: usize, max: usize) -> Result<[f64; N], String> {
+    if args.len() < min || args.len() > max {
+        return Err(if min == max {
+            format!("expected {min} numbers, found {}", args.len())
+        } else {
+            format!("expected {min} to {max} numbers, found {}", args.len())
+        });
+    }
+    // Every number is checked, even the ones after the first `N`
+    let mut values = [0.0; N];
+    for (index, arg) in args.iter().enumerate() {
+        let value = arg.parse().map_err(|_| format!("invalid number `{arg}`"))?;
+        if index < N {
+            values[index] = value;
+        }
+    }
+    Ok(values)
+}
+
+fn parse_color(args: &[&str]) -> Result<Color, String> {
+    // A single value is a gray color
+    if args.len() == 1 {
+        let [gray] = parse_floats::<1>(args, 1, 1)?;
+        return Ok(Color::new(gray, gray, gray));
+    }
+    let [r, g, b] = parse_floats::<3>(args, 3, 3)?;
+    Ok(Color::new(r, g, b))
+}
+
+// Indices of the position, texture coordinate and normal of a face vertex, starting at 0
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct FaceVertex {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+// Parse a face vertex of the form `v`, `v/vt`, `v//vn` or `v/vt/vn`
+fn parse_face_vertex(
+    arg: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+) -> Result<FaceVertex, String> {
+    let mut parts = arg.split('/');
+    let position = resolve_index(parts.next().unwrap_or(""), position_count, "vertex")?;
+    let uv = match parts.next() {
+        Some("") | None => None,
+        Some(index) => Some(resolve_index(index, uv_count, "texture coordinate")?),
+    };
+    let normal = match parts.next() {
+        Some("") | None => None,
+        Some(index) => Some(resolve_index(index, normal_count, "normal")?),
+    };
+    if parts.next().is_some() {
+        return Err(format!("invalid face vertex `{arg}`"));
+    }
+    Ok(FaceVertex {
+        position,
+        uv,
+        normal,
+    })
+}
+
+// Convert a 1-based OBJ index, or a negative index counting back from the end, to a 0-based
+// index into the `count` elements defined so far
+fn resolve_index(index: &str, count: usize, kind: &str) -> Result<usize, String> {
+    let value: i64 = index
+        .parse()
+        .map_err(|_| format!("invalid {kind} index `{index}`"))?;
+    let resolved = if value > 0 {
+        value - 1
+    } else {
+        count as i64 + value
+    };
+    if value == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!(
+            "{kind} index {value} is out of range, {count} defined so far"
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+// Triangles of one material, with the vertices numbered within the group
+struct FaceGroup {
+    material: String,
+    vertices: Vec<FaceVertex>,
+    vertex_index: HashMap<FaceVertex, usize>,
+    triangles: Vec<[usize; 3]>,
+}
+
+impl FaceGroup {
+    fn new(material: String) -> Self {
+        FaceGroup {
+            material,
+            vertices: Vec::new(),
+            vertex_index: HashMap::new(),
+            triangles: Vec::new(),
+        }
+    }
+
+    // Triangulate the polygon as a fan around its first vertex
+    fn add_face(&mut self, face: &[FaceVertex]) {
+        let indices: Vec<usize> = face
+            .iter()
+            .map(|vertex| {
+                *self.vertex_index.entry(*vertex).or_insert_with(|| {
+                    self.vertices.push(*vertex);
+                    self.vertices.len() - 1
+                })
+            })
+            .collect();
+        for k in 1..indices.len() - 1 {
+            self.triangles
+                .push([indices[0], indices[k], indices[k + 1]]);
+        }
+    }
+
+    // Build the mesh, only keeping normals and texture coordinates if every vertex has them
+    fn into_mesh(
+        self,
+        positions: &[Point3],
+        normals: &[Vec3],
+        uvs: &[(f64, f64)],
+        mat: Arc<dyn Material>,
+    ) -> TriangleMesh {
+        let mesh_positions = self
+            .vertices
+            .iter()
+            .map(|vertex| positions[vertex.position].clone())
+            .collect();
+        let mesh_normals = self
+            .vertices
+            .iter()
+            .map(|vertex| vertex.normal.map(|index| normals[index].unit_vector()))
+            .collect::<Option<Vec<Vec3>>>()
+            .unwrap_or_default();
+        let mesh_uvs = self
+            .vertices
+            .iter()
+            .map(|vertex| vertex.uv.map(|index| uvs[index]))
+            .collect::<Option<Vec<(f64, f64)>>>()
+            .unwrap_or_default();
+        TriangleMesh::new(mesh_positions, mesh_normals, mesh_uvs, self.triangles, mat)
+    }
+}
+
+// Parameters of an MTL material which have an equivalent in the renderer
+struct MtlParameters {
+    name: String,
+    kd: Color,     // Diffuse color
+    ks: Color,     // Specular color
+    ns: f64,       // Specular exponent
+    ni: f64,       // Index of refraction
+    dissolve: f64, // Opacity
+    illum: u32,    // Illumination model
+}
+
+impl MtlParameters {
+    fn new(name: String) -> Self {
+        MtlParameters {
+            name,
+            kd: Color::new(0.8, 0.8, 0.8),
+            ks: Color::new(0.0, 0.0, 0.0),
+            ns: 0.0,
+            ni: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+
+    fn to_material(&self) -> Arc<dyn Material> {
+        let max_component = |c: &Color| c.r().max(c.g()).max(c.b());
+
+        if self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9) {
+            return Arc::new(Dielectric::new(self.ni));
+        }
+        let specular = max_component(&self.ks);
+        if specular > 0.0 && (matches!(self.illum, 3 | 5 | 8) || specular > max_component(&self.kd))
+        {
+            // Higher exponents give sharper highlights, i.e. less fuzzy reflections
+            let fuzz = (2.0 / (self.ns + 2.0)).sqrt();
+            return Arc::new(Metal::new(self.ks.clone(), fuzz));
+        }
+        Arc::new(Lambertian::new(self.kd.clone()))
+    }
+}
+
+#[cfg(test)]
+mod obj_tests {
+    use super::*;
+    use crate::{HitRecord, Hittable, IndependentSampler, Interval, Ray};
+    use std::env;
+
+    // File in the temporary directory, removed when dropped
+    struct TempFile {
+        path: PathBuf,
+    }
+
+    impl TempFile {
+        fn new(name: &str, contents: impl AsRef<[u8]>) -> Self {
+            let path = env::temp_dir().join(format!("obj_tests_{}_{name}", std::process::id()));
+            fs::write(&path, contents).unwrap();
+            TempFile { path }
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    fn parse_error_line(obj: &TempFile) -> usize {
+        let default_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        match load_obj(&obj.path, default_material) {
+            Err(ObjError::Parse { line, .. }) => line,
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn test_load_quad_with_negative_indices() {
+        let mut sampler = IndependentSampler::new(0);
+        let mtl = TempFile::new("quad.mtl", "newmtl red\nKd 0.8 0.1 0.1\n");
+        let obj = TempFile::new(
+            "quad.obj",
+            format!(
+                "# unit quad\nmtllib {}\nv -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvn 0 0 1\n\
+                 usemtl red\nf -4//-1 -3//-1 -2//-1 -1//-1\n",
+                mtl.path.file_name().unwrap().to_str().unwrap()
+            ),
+        );
+
+        let default_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let list = load_obj(&obj.path, default_material).unwrap();
+
+        let r = Ray::new(Point3::new(0.5, 0.5, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec: HitRecord = Default::default();
+        assert!(list.hit(&r, Interval::new(0.001, f64::MAX), &mut rec, &mut sampler));
+        assert!((rec.t - 5.0).abs() < 1e-9);
+        assert!(rec.front_face);
+    }
+
+    #[test]
+    fn test_malformed_line_reports_line_number() {
+        let obj = TempFile::new("bad.obj", "v 0 0 0\nv 1 0 0\nv 0 one 0\nf 1 2 3\n");
+        assert_eq!(parse_error_line(&obj), 3);
+    }
+
+    #[test]
+    fn test_missing_mtllib_and_vertex_colors() {
+        let mut sampler = IndependentSampler::new(0);
+        // Vertex colors after the position, and a library which doesn't exist
+        let obj = TempFile::new(
+            "colors.obj",
+            "mtllib missing.mtl\nv -1 -1 0 1 0 0\nv 1 -1 0 0 1 0\nv 0 1 0 0 0 1\n\
+             usemtl red\nf 1 2 3\n",
+        );
+        let default_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let list = load_obj(&obj.path, default_material.clone()).unwrap();
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec: HitRecord = Default::default();
+        assert!(list.hit(&r, Interval::new(0.001, f64::MAX), &mut rec, &mut sampler));
+        assert!((rec.t - 5.0).abs() < 1e-9);
+        let default_material: Arc<dyn Material> = default_material;
+        assert!(Arc::ptr_eq(&rec.mat, &default_material));
+
+        // The extra fields must still be numbers, and there are at most 7 of them
+        let obj = TempFile::new("bad_color.obj", "v 0 0 0\nv 1 2 3 x y z\n");
+        assert_eq!(parse_error_line(&obj), 2);
+        let obj = TempFile::new("long_vertex.obj", "v 1 2 3 4 5 6 7 8\n");
+        assert_eq!(parse_error_line(&obj), 1);
+    }
+
+    #[test]
+    fn test_unreadable_mtllib_is_reported() {
+        let mtl = TempFile::new("latin1.mtl", b"newmtl caf\xe9\n");
+        let obj = TempFile::new(
+            "latin1.obj",
+            format!(
+                "v 0 0 0\nmtllib {}\n",
+                mtl.path.file_name().unwrap().to_str().unwrap()
+            ),
+        );
+        assert_eq!(parse_error_line(&obj), 2);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::Vec3;
+
+/// Orthonormal basis, used to move vectors between world space and a local frame, e.g. one
+/// aligned with the surface normal
+#[derive(Clone, Default)]
+pub struct Onb {
+    axis: [Vec3; 3],
+}
+
+impl Onb {
+    /// Build a basis whose w axis points along `n`, the u and v axes being any perpendicular
+    /// pair
+    pub fn new(n: &Vec3) -> Self {
+        let w = n.unit_vector();
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(&a).unit_vector();
+        let u = v.cross(&w);
+        Onb { axis: [u, v, w] }
+    }
+
+    /// Build a basis whose w axis points along `w` and whose v axis is as close as possible to
+    /// `up`, as for a camera looking towards -w
+    pub fn from_w_up(w: &Vec3, up: &Vec3) -> Self {
+        let w = w.unit_vector();
+        let u = up.cross(&w).unit_vector();
+        let v = w.cross(&u);
+        Onb { axis: [u, v, w] }
+    }
+
+    pub fn u(&self) -> &Vec3 {
+        &self.axis[0]
+    }
+
+    pub fn v(&self) -> &Vec3 {
+        &self.axis[1]
+    }
+
+    pub fn w(&self) -> &Vec3 {
+        &self.axis[2]
+    }
+
+    /// Convert the coordinates of `v` in this basis to world space
+    pub fn transform(&self, v: &Vec3) -> Vec3 {
+        v.x() * &self.axis[0] + v.y() * &self.axis[1] + v.z() * &self.axis[2]
+    }
+
+    /// Convert the world space vector `v` to coordinates in this basis
+    pub fn to_local(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            v.dot(&self.axis[0]),
+            v.dot(&self.axis[1]),
+            v.dot(&self.axis[2]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod onb_tests {
+    use super::*;
+
+    #[test]
+    fn test_basis_is_orthonormal() {
+        for n in [
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(-0.3, 2.0, 0.7),
+        ] {
+            let onb = Onb::new(&n);
+            assert!((onb.w() - n.unit_vector()).length() < 1e-12);
+            for (a, b) in [(onb.u(), onb.v()), (onb.v(), onb.w()), (onb.w(), onb.u())] {
+                assert!(a.dot(b).abs() < 1e-12);
+                assert!((a.length() - 1.0).abs() < 1e-12);
+            }
+            // Right handed
+            assert!((onb.u().cross(onb.v()) - onb.w().clone()).length() < 1e-12);
+
+            let local = Vec3::new(0.2, -0.5, 0.9);
+            assert!((onb.to_local(&onb.transform(&local)) - local).length() < 1e-12);
+        }
+    }
+}
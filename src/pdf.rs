@@ -0,0 +1,191 @@
+use std::f64::consts::PI;
+
+use crate::{Hittable, Onb, Point3, Sampler, Vec3};
+
+/// Probability density over directions, which can generate directions following it
+pub trait Pdf {
+    /// Density of `direction`, with respect to solid angle
+    fn value(&self, direction: &Vec3) -> f64;
+
+    /// Generate a random direction distributed according to the density
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3;
+}
+
+/// Uniform density over all directions
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf {
+    fn value(&self, _direction: &Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        Vec3::random_unit_vector(sampler)
+    }
+}
+
+/// Density proportional to the cosine of the angle with a normal, over the hemisphere around it
+pub struct CosinePdf {
+    uvw: Onb,
+}
+
+impl CosinePdf {
+    pub fn new(w: &Vec3) -> Self {
+        CosinePdf { uvw: Onb::new(w) }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let cosine_theta = direction.unit_vector().dot(self.uvw.w());
+        f64::max(0.0, cosine_theta / PI)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        self.uvw.transform(&Vec3::random_cosine_direction(sampler))
+    }
+}
+
+/// Density of the directions from `origin` towards `objects`, e.g. the lights of a scene
+pub struct HittablePdf<'a> {
+    objects: &'a dyn Hittable,
+    origin: Point3,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(objects: &'a dyn Hittable, origin: &Point3) -> Self {
+        HittablePdf {
+            objects,
+            origin: origin.clone(),
+        }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.objects.pdf_value(&self.origin, direction)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        self.objects.random(&self.origin, sampler)
+    }
+}
+
+/// Weighted average of two densities, generating from either in proportion to its weight
+pub struct MixturePdf<'a> {
+    p: [Box<dyn Pdf + 'a>; 2],
+    // Probability of picking the first density
+    weight: f64,
+}
+
+impl<'a> MixturePdf<'a> {
+    /// Mix the two densities in equal parts
+    pub fn new(p0: Box<dyn Pdf + 'a>, p1: Box<dyn Pdf + 'a>) -> Self {
+        MixturePdf::with_weight(p0, p1, 0.5)
+    }
+
+    /// Mix the densities with the weight `weight` for `p0` and `1 - weight` for `p1`
+    pub fn with_weight(p0: Box<dyn Pdf + 'a>, p1: Box<dyn Pdf + 'a>, weight: f64) -> Self {
+        MixturePdf {
+            p: [p0, p1],
+            weight: weight.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Pdf for MixturePdf<'_> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.weight * self.p[0].value(direction) + (1.0 - self.weight) * self.p[1].value(direction)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        if sampler.get_1d() < self.weight {
+            self.p[0].generate(sampler)
+        } else {
+            self.p[1].generate(sampler)
+        }
+    }
+}
+
+#[cfg(test)]
+mod pdf_tests {
+    use super::*;
+    use crate::IndependentSampler;
+
+    const SAMPLE_COUNT: usize = 100_000;
+
+    // Monte Carlo estimate of the integral of `f` over all directions, from uniform directions
+    fn integrate(f: impl Fn(&Vec3) -> f64, sampler: &mut dyn Sampler) -> f64 {
+        let sum: f64 = (0..SAMPLE_COUNT)
+            .map(|_| f(&SpherePdf.generate(sampler)) * 4.0 * PI)
+            .sum();
+        sum / SAMPLE_COUNT as f64
+    }
+
+    // Average of 1 / value over generated directions, which estimates the solid angle where the
+    // density isn't zero
+    fn support_solid_angle(pdf: &dyn Pdf, sampler: &mut dyn Sampler) -> f64 {
+        let sum: f64 = (0..SAMPLE_COUNT)
+            .map(|_| 1.0 / pdf.value(&pdf.generate(sampler)))
+            .sum();
+        sum / SAMPLE_COUNT as f64
+    }
+
+    #[test]
+    fn test_sphere_pdf() {
+        let mut sampler = IndependentSampler::new(1);
+        assert!((support_solid_angle(&SpherePdf, &mut sampler) - 4.0 * PI).abs() < 1e-9);
+        assert!((integrate(|d| SpherePdf.value(d), &mut sampler) - 1.0).abs() < 1e-9);
+
+        // Uniform directions average to the center of the sphere
+        let mut mean = Vec3::new(0.0, 0.0, 0.0);
+        for _ in 0..SAMPLE_COUNT {
+            let direction = SpherePdf.generate(&mut sampler);
+            assert!((direction.length() - 1.0).abs() < 1e-9);
+            mean = mean + direction / SAMPLE_COUNT as f64;
+        }
+        assert!(mean.length() < 0.01);
+    }
+
+    #[test]
+    fn test_cosine_pdf() {
+        let mut sampler = IndependentSampler::new(2);
+        let normal = Vec3::new(1.0, 2.0, -0.5);
+        let pdf = CosinePdf::new(&normal);
+
+        // Normalized over the hemisphere around the normal, which holds every generated
+        // direction; their average cosine is 2/3. Averaging 1 / value would have an unbounded
+        // variance near the horizon
+        assert!((integrate(|d| pdf.value(d), &mut sampler) - 1.0).abs() < 0.02);
+        let mut sum = 0.0;
+        for _ in 0..SAMPLE_COUNT {
+            let direction = pdf.generate(&mut sampler);
+            assert!(pdf.value(&direction) > 0.0);
+            sum += direction.unit_vector().dot(&normal.unit_vector());
+        }
+        assert!((sum / SAMPLE_COUNT as f64 - 2.0 / 3.0).abs() < 0.01);
+        assert_eq!(pdf.value(&-normal), 0.0);
+    }
+
+    #[test]
+    fn test_mixture_pdf() {
+        let mut sampler = IndependentSampler::new(3);
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let mixture = MixturePdf::new(Box::new(CosinePdf::new(&normal)), Box::new(SpherePdf));
+
+        for _ in 0..100 {
+            let direction = SpherePdf.generate(&mut sampler);
+            let expected =
+                0.5 * CosinePdf::new(&normal).value(&direction) + 0.5 * SpherePdf.value(&direction);
+            assert!((mixture.value(&direction) - expected).abs() < 1e-12);
+        }
+        assert!((integrate(|d| mixture.value(d), &mut sampler) - 1.0).abs() < 0.02);
+        assert!((support_solid_angle(&mixture, &mut sampler) - 4.0 * PI).abs() < 0.1);
+
+        // Only the uniform half generates directions below the hemisphere, half the time
+        let below = (0..SAMPLE_COUNT)
+            .filter(|_| mixture.generate(&mut sampler).z() < 0.0)
+            .count();
+        assert!((below as f64 / SAMPLE_COUNT as f64 - 0.25).abs() < 0.01);
+    }
+}
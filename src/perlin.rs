@@ -0,0 +1,158 @@
+use crate::{Point3, Vec3};
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+const POINT_COUNT: usize = 256;
+
+/// Perlin gradient noise
+///
+/// The lattice gradients and permutations only depend on the seed, so the same seed always
+/// gives the same noise
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| random_unit_vector(&mut rng))
+            .collect();
+
+        Perlin {
+            gradients,
+            perm_x: generate_perm(&mut rng),
+            perm_y: generate_perm(&mut rng),
+            perm_z: generate_perm(&mut rng),
+        }
+    }
+
+    /// Noise value at `p`, roughly in [-1, 1]
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let u = p.x() - p.x().floor();
+        let v = p.y() - p.y().floor();
+        let w = p.z() - p.z().floor();
+
+        let i = p.x().floor() as i64;
+        let j = p.y().floor() as i64;
+        let k = p.z().floor() as i64;
+
+        let mut c: [[[Vec3; 2]; 2]; 2] = Default::default();
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, gradient) in row.iter_mut().enumerate() {
+                    let index = self.perm_x[((i + di as i64) & 255) as usize]
+                        ^ self.perm_y[((j + dj as i64) & 255) as usize]
+                        ^ self.perm_z[((k + dk as i64) & 255) as usize];
+                    *gradient = self.gradients[index].clone();
+                }
+            }
+        }
+
+        perlin_interp(&c, u, v, w)
+    }
+
+    /// Sum of the absolute noise over `depth` octaves, each at twice the frequency and half the
+    /// amplitude of the previous one
+    pub fn turbulence(&self, p: &Point3, depth: usize) -> f64 {
+        let mut accumulator = 0.0;
+        let mut temp_p = p.clone();
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accumulator += weight * self.noise(&temp_p).abs();
+            weight *= 0.5;
+            temp_p = Point3::new(temp_p.x() * 2.0, temp_p.y() * 2.0, temp_p.z() * 2.0);
+        }
+
+        accumulator
+    }
+
+    /// Fractal Brownian motion: sum of the signed noise over `octaves` octaves, the frequency
+    /// growing by `lacunarity` and the amplitude by `gain` at every octave
+    ///
+    /// The result is normalized by the sum of the amplitudes, so it stays roughly in [-1, 1]
+    pub fn fbm(&self, p: &Point3, octaves: usize, lacunarity: f64, gain: f64) -> f64 {
+        let mut accumulator = 0.0;
+        let mut total_weight = 0.0;
+        let mut frequency = 1.0;
+        let mut weight = 1.0;
+
+        for _ in 0..octaves {
+            let temp_p = Point3::new(p.x() * frequency, p.y() * frequency, p.z() * frequency);
+            accumulator += weight * self.noise(&temp_p);
+            total_weight += weight;
+            frequency *= lacunarity;
+            weight *= gain;
+        }
+
+        if total_weight > 0.0 {
+            accumulator / total_weight
+        } else {
+            0.0
+        }
+    }
+}
+
+fn generate_perm(rng: &mut SmallRng) -> Vec<usize> {
+    let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+    perm.shuffle(rng);
+    perm
+}
+
+fn random_unit_vector(rng: &mut SmallRng) -> Vec3 {
+    let z: f64 = rng.random_range(-1.0..1.0);
+    let phi: f64 = rng.random_range(0.0..std::f64::consts::TAU);
+    let r = (1.0 - z * z).sqrt();
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// Trilinear interpolation of the dot products between the lattice gradients and the offsets to
+// the corners, smoothed with the Hermite cubic so the noise has no visible grid
+fn perlin_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+    let uu = u * u * (3.0 - 2.0 * u);
+    let vv = v * v * (3.0 - 2.0 * v);
+    let ww = w * w * (3.0 - 2.0 * w);
+
+    let mut accumulator = 0.0;
+    for (i, plane) in c.iter().enumerate() {
+        for (j, row) in plane.iter().enumerate() {
+            for (k, gradient) in row.iter().enumerate() {
+                let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                let weight = Vec3::new(u - fi, v - fj, w - fk);
+                accumulator += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                    * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                    * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                    * gradient.dot(&weight);
+            }
+        }
+    }
+
+    accumulator
+}
+
+#[cfg(test)]
+mod perlin_tests {
+    use super::*;
+
+    #[test]
+    fn test_noise_is_seeded() {
+        let p = Point3::new(1.3, -4.7, 2.25);
+        assert_eq!(Perlin::new(7).noise(&p), Perlin::new(7).noise(&p));
+        assert_ne!(Perlin::new(7).noise(&p), Perlin::new(8).noise(&p));
+
+        // The offset to the closest corner is zero at lattice points, so the noise vanishes
+        assert_eq!(Perlin::new(7).noise(&Point3::new(3.0, -2.0, 5.0)), 0.0);
+
+        let noise = Perlin::new(7);
+        for i in 0..1000 {
+            let p = Point3::new(i as f64 * 0.37, i as f64 * 0.11, i as f64 * -0.23);
+            assert!(noise.noise(&p).abs() <= 1.0);
+            assert!(noise.fbm(&p, 5, 2.0, 0.5).abs() <= 1.0);
+        }
+    }
+}
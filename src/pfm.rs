@@ -0,0 +1,58 @@
+use crate::Film;
+
+/// Encode the linear radiance of `film` as a color Portable Float Map
+///
+/// Samples are little endian 32-bit floats, and rows are stored from the bottom of the image
+pub fn encode_pfm(film: &Film) -> Vec<u8> {
+    let (width, height) = (film.width(), film.height());
+    // A negative scale marks little endian data
+    let mut output = format!("PF\n{width} {height}\n-1.0\n").into_bytes();
+    output.reserve(width * height * 12);
+
+    for y in (0..height).rev() {
+        for x in 0..width {
+            let pixel = film.pixel(x, y);
+            for value in [pixel.r(), pixel.g(), pixel.b()] {
+                output.extend_from_slice(&(value as f32).to_le_bytes());
+            }
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod pfm_tests {
+    use super::*;
+    use crate::Color;
+
+    #[test]
+    fn test_rows_are_written_bottom_to_top() {
+        let (width, height) = (2, 3);
+        let mut film = Film::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let value = (10 * y + x) as f64;
+                film.add_sample(x, y, Color::new(value, value + 0.25, value + 0.5));
+            }
+        }
+        let bytes = encode_pfm(&film);
+
+        let header = b"PF\n2 3\n-1.0\n";
+        assert_eq!(bytes[..header.len()], header[..]);
+        let data = &bytes[header.len()..];
+        assert_eq!(data.len(), width * height * 12);
+
+        let values: Vec<f32> = data
+            .chunks_exact(4)
+            .map(|sample| f32::from_le_bytes(sample.try_into().unwrap()))
+            .collect();
+        for (row, y) in (0..height).rev().enumerate() {
+            for x in 0..width {
+                let value = (10 * y + x) as f32;
+                let start = 3 * (row * width + x);
+                assert_eq!(values[start..start + 3], [value, value + 0.25, value + 0.5]);
+            }
+        }
+    }
+}
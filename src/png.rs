@@ -0,0 +1,466 @@
+use crate::{BitDepth, Color, Image, ImageError, image::quantize, zlib};
+
+/// First 8 bytes of every PNG file
+pub const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+// Starting column, starting row, column step and row step of the 7 Adam7 interlacing passes
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Encode an RGB PNG image with 8 or 16 bits per channel
+///
+/// The pixels of `image` are written as they are, so they should already be gamma encoded
+pub fn encode_png(image: &Image, bit_depth: BitDepth) -> Vec<u8> {
+    let (depth, bytes_per_pixel) = match bit_depth {
+        BitDepth::Eight => (8, 3),
+        BitDepth::Sixteen => (16, 6),
+    };
+    let stride = image.width() * bytes_per_pixel;
+
+    let mut raw: Vec<u8> = Vec::with_capacity(stride * image.height());
+    for pixel in image.pixels() {
+        for value in [pixel.r(), pixel.g(), pixel.b()] {
+            match bit_depth {
+                BitDepth::Eight => raw.push(quantize(value, 255) as u8),
+                BitDepth::Sixteen => raw.extend_from_slice(&quantize(value, 65535).to_be_bytes()),
+            }
+        }
+    }
+    let filtered = filter(&raw, stride, bytes_per_pixel);
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(image.width() as u32).to_be_bytes());
+    header.extend_from_slice(&(image.height() as u32).to_be_bytes());
+    // Truecolor, default compression and filtering, not interlaced
+    header.extend_from_slice(&[depth, 2, 0, 0, 0]);
+
+    let mut output = SIGNATURE.to_vec();
+    write_chunk(&mut output, b"IHDR", &header);
+    write_chunk(&mut output, b"IDAT", &zlib::compress(&filtered));
+    write_chunk(&mut output, b"IEND", &[]);
+    output
+}
+
+/// Decode a PNG image
+///
+/// Supports every color type and bit depth of the PNG specification, and interlaced images.
+/// The alpha channel is dropped.
+pub fn decode_png(data: &[u8]) -> Result<Image, ImageError> {
+    let error = |message: &str| ImageError::Decode(format!("PNG: {message}"));
+
+    if !data.starts_with(&SIGNATURE) {
+        return Err(error("missing signature"));
+    }
+
+    let mut header: Option<Header> = None;
+    let mut palette: Vec<Color> = Vec::new();
+    let mut compressed: Vec<u8> = Vec::new();
+
+    let mut position = SIGNATURE.len();
+    loop {
+        let Some(length_bytes) = data.get(position..position + 4) else {
+            return Err(error("missing IEND chunk"));
+        };
+        let length = u32::from_be_bytes(length_bytes.try_into().unwrap()) as usize;
+        let Some(chunk) = data.get(position + 4..position + 8 + length) else {
+            return Err(error("truncated chunk"));
+        };
+        let Some(crc_bytes) = data.get(position + 8 + length..position + 12 + length) else {
+            return Err(error("truncated chunk"));
+        };
+        if u32::from_be_bytes(crc_bytes.try_into().unwrap()) != crc32(chunk) {
+            return Err(error("chunk CRC mismatch"));
+        }
+        position += 12 + length;
+
+        let (chunk_type, chunk_data) = chunk.split_at(4);
+        match chunk_type {
+            b"IHDR" => header = Some(Header::parse(chunk_data).map_err(|m| error(&m))?),
+            b"PLTE" => {
+                palette = chunk_data
+                    .chunks_exact(3)
+                    .map(|rgb| {
+                        Color::new(
+                            f64::from(rgb[0]) / 255.0,
+                            f64::from(rgb[1]) / 255.0,
+                            f64::from(rgb[2]) / 255.0,
+                        )
+                    })
+                    .collect()
+            }
+            b"IDAT" => compressed.extend_from_slice(chunk_data),
+            b"IEND" => break,
+            // Ancillary chunks (transparency, gamma, text, ...) are ignored
+            _ => {}
+        }
+    }
+
+    let Some(header) = header else {
+        return Err(error("missing IHDR chunk"));
+    };
+    if header.color_type == 3 && palette.is_empty() {
+        return Err(error("missing PLTE chunk"));
+    }
+    let filtered = zlib::decompress(&compressed).map_err(|m| error(&m))?;
+
+    let passes: &[(usize, usize, usize, usize)] = if header.interlaced {
+        &ADAM7_PASSES
+    } else {
+        &[(0, 0, 1, 1)]
+    };
+
+    // The size comes from the header, so check it against the actual data before allocating
+    let expected_size = passes
+        .iter()
+        .try_fold(0usize, |total, &pass| {
+            total.checked_add(header.pass_size(pass)?)
+        })
+        .ok_or_else(|| error("image is too large"))?;
+    if expected_size > filtered.len() {
+        return Err(error("not enough image data"));
+    }
+    let pixel_count = header
+        .width
+        .checked_mul(header.height)
+        .ok_or_else(|| error("image is too large"))?;
+
+    let mut pixels = vec![Color::default(); pixel_count];
+    let mut offset = 0;
+    for &(x0, y0, dx, dy) in passes {
+        let pass_width = (header.width + dx - 1 - x0) / dx;
+        let pass_height = (header.height + dy - 1 - y0) / dy;
+        if pass_width == 0 || pass_height == 0 {
+            continue;
+        }
+
+        let stride = (pass_width * header.bits_per_pixel()).div_ceil(8);
+        let pass_size = (stride + 1) * pass_height;
+        let pass_data = &filtered[offset..offset + pass_size];
+        offset += pass_size;
+
+        let scanlines = unfilter(pass_data, stride, header.bits_per_pixel().div_ceil(8))
+            .map_err(|m| error(&m))?;
+        for (row, scanline) in scanlines.chunks_exact(stride).enumerate() {
+            for column in 0..pass_width {
+                let x = x0 + column * dx;
+                let y = y0 + row * dy;
+                pixels[y * header.width + x] = header.pixel(scanline, column, &palette);
+            }
+        }
+    }
+
+    Ok(Image::new(header.width, header.height, pixels))
+}
+
+/// CRC-32 checksum of `data`, as used by PNG chunks
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            // Polynomial 0x04c11db7 with the bits reversed
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: usize,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Header, String> {
+        if data.len() != 13 {
+            return Err("invalid IHDR chunk".to_string());
+        }
+        let header = Header {
+            width: u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize,
+            height: u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize,
+            bit_depth: data[8] as usize,
+            color_type: data[9],
+            interlaced: data[12] == 1,
+        };
+
+        let valid_depths: &[usize] = match header.color_type {
+            0 => &[1, 2, 4, 8, 16],
+            3 => &[1, 2, 4, 8],
+            2 | 4 | 6 => &[8, 16],
+            _ => return Err(format!("unknown color type {}", header.color_type)),
+        };
+        if !valid_depths.contains(&header.bit_depth) {
+            return Err(format!(
+                "bit depth {} isn't allowed for color type {}",
+                header.bit_depth, header.color_type
+            ));
+        }
+        if data[10] != 0 || data[11] != 0 || data[12] > 1 {
+            return Err("unknown compression, filter or interlace method".to_string());
+        }
+        if header.width == 0 || header.height == 0 {
+            return Err("image has no pixels".to_string());
+        }
+        Ok(header)
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    // Size of the filtered scanlines of the pass starting at (`x0`, `y0`) with steps `dx` and
+    // `dy`, or `None` if it overflows
+    fn pass_size(&self, (x0, y0, dx, dy): (usize, usize, usize, usize)) -> Option<usize> {
+        let pass_width = (self.width + dx - 1 - x0) / dx;
+        let pass_height = (self.height + dy - 1 - y0) / dy;
+        if pass_width == 0 || pass_height == 0 {
+            return Some(0);
+        }
+        let stride = pass_width.checked_mul(self.bits_per_pixel())?.div_ceil(8);
+        stride.checked_add(1)?.checked_mul(pass_height)
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth
+    }
+
+    // Read the `channel`th sample of the pixel in `column`
+    fn sample(&self, scanline: &[u8], column: usize, channel: usize) -> u16 {
+        let index = column * self.channels() + channel;
+        match self.bit_depth {
+            16 => u16::from_be_bytes([scanline[2 * index], scanline[2 * index + 1]]),
+            8 => u16::from(scanline[index]),
+            depth => {
+                // Samples smaller than a byte are packed starting from the high bits
+                let bit = index * depth;
+                let shift = 8 - depth - bit % 8;
+                u16::from((scanline[bit / 8] >> shift) & ((1 << depth) - 1) as u8)
+            }
+        }
+    }
+
+    fn pixel(&self, scanline: &[u8], column: usize, palette: &[Color]) -> Color {
+        if self.color_type == 3 {
+            let index = self.sample(scanline, column, 0) as usize;
+            return palette.get(index).cloned().unwrap_or_default();
+        }
+
+        let max_value = ((1u32 << self.bit_depth) - 1) as f64;
+        let value = |channel: usize| f64::from(self.sample(scanline, column, channel)) / max_value;
+        match self.color_type {
+            // Grayscale, with or without alpha
+            0 | 4 => Color::new(value(0), value(0), value(0)),
+            // RGB, with or without alpha
+            _ => Color::new(value(0), value(1), value(2)),
+        }
+    }
+}
+
+fn write_chunk(output: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = output.len();
+    output.extend_from_slice(chunk_type);
+    output.extend_from_slice(data);
+    let crc = crc32(&output[start..]);
+    output.extend_from_slice(&crc.to_be_bytes());
+}
+
+// Filter every scanline of `data` and prefix it with its filter type
+//
+// Each row uses the filter giving the smallest sum of absolute differences, as signed bytes,
+// which usually compresses best
+fn filter(data: &[u8], stride: usize, bytes_per_pixel: usize) -> Vec<u8> {
+    let rows = data.len().checked_div(stride).unwrap_or(0);
+    let mut output: Vec<u8> = Vec::with_capacity(rows * (stride + 1));
+    let zero_row = vec![0u8; stride];
+    let mut candidate = vec![0u8; stride];
+    let mut best = vec![0u8; stride];
+
+    for row in 0..rows {
+        let line = &data[row * stride..(row + 1) * stride];
+        let previous = if row > 0 {
+            &data[(row - 1) * stride..row * stride]
+        } else {
+            &zero_row
+        };
+
+        let mut best_type = 0;
+        let mut best_cost = u64::MAX;
+        for filter_type in 0..5u8 {
+            for (i, &byte) in line.iter().enumerate() {
+                let left = if i >= bytes_per_pixel {
+                    line[i - bytes_per_pixel]
+                } else {
+                    0
+                };
+                let up = previous[i];
+                let up_left = if i >= bytes_per_pixel {
+                    previous[i - bytes_per_pixel]
+                } else {
+                    0
+                };
+                let predictor = match filter_type {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((u16::from(left) + u16::from(up)) / 2) as u8,
+                    _ => paeth(left, up, up_left),
+                };
+                candidate[i] = byte.wrapping_sub(predictor);
+            }
+            let cost = candidate
+                .iter()
+                .map(|&byte| u64::from((byte as i8).unsigned_abs()))
+                .sum();
+            if cost < best_cost {
+                best_cost = cost;
+                best_type = filter_type;
+                std::mem::swap(&mut best, &mut candidate);
+            }
+        }
+
+        output.push(best_type);
+        output.extend_from_slice(&best);
+    }
+
+    output
+}
+
+// Undo the filter applied to every scanline, each of which starts with its filter type
+//
+// `bytes_per_pixel` is the distance to the byte of the previous pixel which the filters refer
+// to, rounded up to 1 for images with less than a byte per pixel
+fn unfilter(data: &[u8], stride: usize, bytes_per_pixel: usize) -> Result<Vec<u8>, String> {
+    let mut output: Vec<u8> = Vec::with_capacity(data.len());
+    let previous_row = vec![0u8; stride];
+
+    for (row, line) in data.chunks_exact(stride + 1).enumerate() {
+        let (filter_type, filtered) = (line[0], &line[1..]);
+        let start = output.len();
+        for (i, &byte) in filtered.iter().enumerate() {
+            let left = if i >= bytes_per_pixel {
+                output[start + i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let (up, up_left) = if row > 0 {
+                let previous = start - stride;
+                (
+                    output[previous + i],
+                    if i >= bytes_per_pixel {
+                        output[previous + i - bytes_per_pixel]
+                    } else {
+                        0
+                    },
+                )
+            } else {
+                (previous_row[i], 0)
+            };
+
+            let predictor = match filter_type {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((u16::from(left) + u16::from(up)) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(format!("unknown filter type {filter_type}")),
+            };
+            output.push(byte.wrapping_add(predictor));
+        }
+    }
+    Ok(output)
+}
+
+// Pick whichever of the left, up and up-left bytes is closest to left + up - up_left
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let pa = (p - i16::from(a)).abs();
+    let pb = (p - i16::from(b)).abs();
+    let pc = (p - i16::from(c)).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod png_tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let (width, height) = (37, 23);
+        let pixels = (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as f64, (i / width) as f64);
+                // Smooth gradients, repeated rows and a few out of range values
+                Color::new(
+                    x / width as f64,
+                    (y / 4.0).floor() / 6.0,
+                    (x * y).sin() * 1.2,
+                )
+            })
+            .collect();
+        let image = Image::new(width, height, pixels);
+
+        for (bit_depth, max_value) in [(BitDepth::Eight, 255.0), (BitDepth::Sixteen, 65535.0)] {
+            let decoded = decode_png(&encode_png(&image, bit_depth)).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (width, height));
+            for (a, b) in image.pixels().iter().zip(decoded.pixels()) {
+                for (expected, actual) in [(a.r(), b.r()), (a.g(), b.g()), (a.b(), b.b())] {
+                    let expected = (expected.clamp(0.0, 1.0) * (max_value + 1.0)).min(max_value);
+                    assert_eq!(actual * max_value, expected.floor());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_huge_header_is_rejected() {
+        for (width, height) in [(0x7fff_ffffu32, 0x7fff_ffffu32), (100_000, 100_000), (1, 2)] {
+            let mut header = Vec::new();
+            header.extend_from_slice(&width.to_be_bytes());
+            header.extend_from_slice(&height.to_be_bytes());
+            // RGB with 16 bits per channel
+            header.extend_from_slice(&[16, 2, 0, 0, 0]);
+
+            let mut data = SIGNATURE.to_vec();
+            write_chunk(&mut data, b"IHDR", &header);
+            // The scanline of a single pixel
+            write_chunk(&mut data, b"IDAT", &zlib::compress(&[0; 7]));
+            write_chunk(&mut data, b"IEND", &[]);
+            assert!(decode_png(&data).is_err());
+        }
+    }
+
+    #[test]
+    fn test_compress_round_trip() {
+        let mut data: Vec<u8> = b"abcabcabcabd".repeat(5000);
+        data.extend((0..70000u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8));
+        for input in [&data[..], &[][..], &data[..2]] {
+            assert_eq!(zlib::decompress(&zlib::compress(input)).unwrap(), input);
+        }
+        // Repetitive data shrinks, random data is stored
+        assert!(zlib::compress(&data[..60000]).len() < 1000);
+        assert!(zlib::compress(&data).len() < data.len() - 50000);
+    }
+}
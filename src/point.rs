@@ -1,5 +1,5 @@
 use crate::vec::Vec3;
-use std::ops::{Add, Sub};
+use std::ops::{Add, Index, Sub};
 
 /// Represent points in 3D space
 #[derive(Clone, Default)]
@@ -21,6 +21,19 @@ impl Point3 {
     pub fn z(&self) -> f64 {
         self.0.z()
     }
+
+    /// Get the vector from the origin to the point
+    pub fn as_vec3(&self) -> &Vec3 {
+        &self.0
+    }
+}
+
+impl Index<usize> for Point3 {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &Self::Output {
+        &self.0[axis]
+    }
 }
 
 impl Add<Vec3> for Point3 {
@@ -0,0 +1,205 @@
+use crate::{BitDepth, Color, Image, ImageError, image::quantize};
+
+/// Encode a plain (P3) PPM image with 8 bits per channel
+///
+/// The pixels of `image` are written as they are, so they should already be gamma encoded
+pub fn encode_plain_ppm(image: &Image) -> Vec<u8> {
+    let mut output = format!("P3\n{} {}\n255\n", image.width(), image.height());
+    for pixel in image.pixels() {
+        let [r, g, b] = [pixel.r(), pixel.g(), pixel.b()].map(|value| quantize(value, 255));
+        output.push_str(&format!("{r} {g} {b}\n"));
+    }
+    output.into_bytes()
+}
+
+/// Encode a raw (P6) PPM image with 8 or 16 bits per channel
+///
+/// The pixels of `image` are written as they are, so they should already be gamma encoded
+pub fn encode_ppm(image: &Image, bit_depth: BitDepth) -> Vec<u8> {
+    let max_value = match bit_depth {
+        BitDepth::Eight => 255,
+        BitDepth::Sixteen => 65535,
+    };
+    let mut output =
+        format!("P6\n{} {}\n{max_value}\n", image.width(), image.height()).into_bytes();
+    for pixel in image.pixels() {
+        for value in [pixel.r(), pixel.g(), pixel.b()] {
+            let sample = quantize(value, max_value);
+            match bit_depth {
+                BitDepth::Eight => output.push(sample as u8),
+                // Samples larger than a byte are big endian
+                BitDepth::Sixteen => output.extend_from_slice(&sample.to_be_bytes()),
+            }
+        }
+    }
+    output
+}
+
+/// Decode a PPM or PGM image, in either the plain (P3, P2) or the raw (P6, P5) format
+///
+/// Samples are scaled by the maximum value of the header, which can be up to 65535. Grayscale
+/// images get the same value in every channel
+pub fn decode_ppm(data: &[u8]) -> Result<Image, ImageError> {
+    let error = |message: &str| ImageError::Decode(format!("PPM: {message}"));
+
+    let mut reader = HeaderReader { data, position: 0 };
+    let magic = reader
+        .token()
+        .ok_or_else(|| error("missing magic number"))?;
+    let (channels, binary) = match magic {
+        b"P2" => (1, false),
+        b"P3" => (3, false),
+        b"P5" => (1, true),
+        b"P6" => (3, true),
+        _ => return Err(error("only the P2, P3, P5 and P6 formats are supported")),
+    };
+    let width = reader.number().map_err(|m| error(&m))?;
+    let height = reader.number().map_err(|m| error(&m))?;
+    let max_value = reader.number().map_err(|m| error(&m))?;
+    if max_value == 0 || max_value > 65535 {
+        return Err(error("maximum value must be between 1 and 65535"));
+    }
+    let scale = 1.0 / max_value as f64;
+
+    let sample_count = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(channels))
+        .ok_or_else(|| error("truncated or oversized image"))?;
+    let samples: Vec<usize> = if binary {
+        // A single whitespace byte separates the header from the binary samples
+        let start = reader.position + 1;
+        let bytes_per_sample = if max_value > 255 { 2 } else { 1 };
+        let Some(raw) = sample_count
+            .checked_mul(bytes_per_sample)
+            .and_then(|size| size.checked_add(start))
+            .and_then(|end| data.get(start..end))
+        else {
+            return Err(error("truncated or oversized image"));
+        };
+        if bytes_per_sample == 2 {
+            raw.chunks_exact(2)
+                .map(|pair| usize::from(u16::from_be_bytes([pair[0], pair[1]])))
+                .collect()
+        } else {
+            raw.iter().map(|&byte| usize::from(byte)).collect()
+        }
+    } else {
+        (0..sample_count)
+            .map(|_| reader.number())
+            .collect::<Result<_, _>>()
+            .map_err(|m| error(&m))?
+    };
+
+    if samples.iter().any(|&sample| sample > max_value) {
+        return Err(error("sample is larger than the maximum value"));
+    }
+    let pixels = samples
+        .chunks_exact(channels)
+        .map(|samples| match samples {
+            [gray] => {
+                let value = *gray as f64 * scale;
+                Color::new(value, value, value)
+            }
+            _ => Color::new(
+                samples[0] as f64 * scale,
+                samples[1] as f64 * scale,
+                samples[2] as f64 * scale,
+            ),
+        })
+        .collect();
+    Ok(Image::new(width, height, pixels))
+}
+
+// Splits the text part of a PPM file into tokens, skipping whitespace and `#` comments
+struct HeaderReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> HeaderReader<'a> {
+    fn token(&mut self) -> Option<&'a [u8]> {
+        loop {
+            match self.data.get(self.position)? {
+                b'#' => {
+                    while self.data.get(self.position).is_some_and(|&b| b != b'\n') {
+                        self.position += 1;
+                    }
+                }
+                byte if byte.is_ascii_whitespace() => self.position += 1,
+                _ => break,
+            }
+        }
+
+        let start = self.position;
+        while self
+            .data
+            .get(self.position)
+            .is_some_and(|&b| !b.is_ascii_whitespace() && b != b'#')
+        {
+            self.position += 1;
+        }
+        Some(&self.data[start..self.position])
+    }
+
+    fn number(&mut self) -> Result<usize, String> {
+        let token = self.token().ok_or("file ended unexpectedly")?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|text| text.parse().ok())
+            .ok_or_else(|| {
+                format!(
+                    "expected a number, found {:?}",
+                    String::from_utf8_lossy(token)
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod ppm_tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let pixels = (0..12)
+            .map(|i| Color::new(i as f64 / 11.0, 0.5, 1.0 - i as f64 / 11.0))
+            .collect();
+        let image = Image::new(4, 3, pixels);
+
+        for (data, max_value) in [
+            (encode_plain_ppm(&image), 255.0),
+            (encode_ppm(&image, BitDepth::Eight), 255.0),
+            (encode_ppm(&image, BitDepth::Sixteen), 65535.0),
+        ] {
+            let decoded = decode_ppm(&data).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (4, 3));
+            for (a, b) in image.pixels().iter().zip(decoded.pixels()) {
+                for (expected, actual) in [(a.r(), b.r()), (a.g(), b.g()), (a.b(), b.b())] {
+                    assert!((expected - actual).abs() <= 1.0 / max_value);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_pgm() {
+        // Comments and irregular whitespace between the tokens
+        let plain = b"P2 # gray\n2\t2 #size\n\n  10\n0 5\r\n 10\n# last row\n7\n";
+        let raw = b"P5\n# comment\n2 2\n10\n\x00\x05\x0a\x07";
+        for data in [&plain[..], &raw[..]] {
+            let image = decode_ppm(data).unwrap();
+            for (pixel, expected) in image.pixels().iter().zip([0.0, 0.5, 1.0, 0.7]) {
+                assert!((pixel.g() - expected).abs() < 1e-12);
+            }
+            assert_eq!(image.pixel(1, 1).r(), image.pixel(1, 1).b());
+        }
+
+        assert!(decode_ppm(b"P5\n2 2\n10\n\x00\x05\x0b\x07").is_err());
+        assert!(decode_ppm(b"P5\n2 2\n10\n\x00\x05").is_err());
+
+        // Sizes overflowing when multiplied by the channels or the bytes per sample
+        assert!(decode_ppm(b"P6 4611686018427387904 1 65535\n\x00\x00").is_err());
+        assert!(decode_ppm(b"P5 4611686018427387904 2 65535\n\x00\x00").is_err());
+        assert!(decode_ppm(b"P5 9223372036854775807 1 65535\n\x00\x00").is_err());
+    }
+}
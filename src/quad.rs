@@ -0,0 +1,254 @@
+use crate::{
+    Aabb, HitRecord, Hittable, HittableList, Interval, Material, Point3, Ray, Sampler, Vec3,
+};
+use std::sync::Arc;
+
+/// Parallelogram given by a corner `q` and the two edges `u` and `v` starting at that corner
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3, // Constant used to find the planar coordinates of a point, n / (n . n)
+    mat: Arc<dyn Material>,
+    bbox: Aabb,
+    normal: Vec3, // Unit normal of the plane containing the quad
+    d: f64,       // Constant `D` of the plane equation n . p = D
+    area: f64,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Arc<dyn Material>) -> Self {
+        let n = u.cross(&v);
+        let normal = n.unit_vector();
+        let d = normal.dot(q.as_vec3());
+        let w = n.clone() / n.dot(&n);
+        let area = n.length();
+
+        // Box enclosing all four vertices
+        let bbox_diagonal1 = Aabb::from_points(&q, &(q.clone() + u.clone() + v.clone()));
+        let bbox_diagonal2 = Aabb::from_points(&(q.clone() + u.clone()), &(q.clone() + v.clone()));
+        let bbox = Aabb::surrounding(&bbox_diagonal1, &bbox_diagonal2);
+
+        Quad {
+            q,
+            u,
+            v,
+            w,
+            mat,
+            bbox,
+            normal,
+            d,
+            area,
+        }
+    }
+
+    /// Intersect the ray with the plane of the quad and then check whether the intersection lies
+    /// inside the quad using its planar coordinates
+    fn intersect(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let denom = self.normal.dot(r.direction());
+
+        // No hit if the ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        // Return false if the hit point parameter t is outside the ray interval
+        let t = (self.d - self.normal.dot(r.origin().as_vec3())) / denom;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+
+        // Determine if the hit point lies within the planar shape using its plane coordinates
+        let intersection = r.at(t);
+        let planar_hitpt_vector = &intersection - &self.q;
+        let alpha = self.w.dot(&planar_hitpt_vector.cross(&self.v));
+        let beta = self.w.dot(&self.u.cross(&planar_hitpt_vector));
+
+        let unit_interval = Interval::new(0.0, 1.0);
+        if !unit_interval.contains(alpha) || !unit_interval.contains(beta) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = intersection;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.mat = Arc::clone(&self.mat);
+        rec.set_face_normal(r, self.normal.clone());
+
+        true
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> bool {
+        self.intersect(r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox.clone()
+    }
+
+    /// Uniform density over the area of the quad, converted to solid angle from `origin`
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let mut rec = HitRecord::default();
+        let r = Ray::new(origin.clone(), direction.clone());
+        if !self.intersect(&r, Interval::new(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (direction.dot(&rec.normal) / direction.length()).abs();
+
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let (r1, r2) = sampler.get_2d();
+        let p = self.q.clone() + (r1 * &self.u) + (r2 * &self.v);
+        p - origin.clone()
+    }
+}
+
+/// Create the 6 sided box (cuboid) which has the opposite vertices `a` and `b`
+pub fn make_box(a: &Point3, b: &Point3, mat: Arc<dyn Material>) -> HittableList {
+    let mut sides: HittableList = Default::default();
+
+    // Construct the two opposite vertices with the minimum and maximum coordinates
+    let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+    let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+    // Front
+    sides.add(Arc::new(Quad::new(
+        Point3::new(min.x(), min.y(), max.z()),
+        dx.clone(),
+        dy.clone(),
+        mat.clone(),
+    )));
+    // Right
+    sides.add(Arc::new(Quad::new(
+        Point3::new(max.x(), min.y(), max.z()),
+        -&dz,
+        dy.clone(),
+        mat.clone(),
+    )));
+    // Back
+    sides.add(Arc::new(Quad::new(
+        Point3::new(max.x(), min.y(), min.z()),
+        -&dx,
+        dy.clone(),
+        mat.clone(),
+    )));
+    // Left
+    sides.add(Arc::new(Quad::new(
+        Point3::new(min.x(), min.y(), min.z()),
+        dz.clone(),
+        dy,
+        mat.clone(),
+    )));
+    // Top
+    sides.add(Arc::new(Quad::new(
+        Point3::new(min.x(), max.y(), max.z()),
+        dx.clone(),
+        -&dz,
+        mat.clone(),
+    )));
+    // Bottom
+    sides.add(Arc::new(Quad::new(
+        Point3::new(min.x(), min.y(), min.z()),
+        dx,
+        dz,
+        mat,
+    )));
+
+    sides
+}
+
+#[cfg(test)]
+mod quad_tests {
+    use super::*;
+    use crate::{IndependentSampler, Lambertian};
+
+    // Unit square in the z = -1 plane, with its corner at (-0.5, -0.5)
+    fn unit_square() -> Quad {
+        Quad::new(
+            Point3::new(-0.5, -0.5, -1.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Arc::new(Lambertian::default()),
+        )
+    }
+
+    fn hit(quad: &Quad, origin: Point3, direction: Vec3) -> Option<HitRecord> {
+        let mut rec = HitRecord::default();
+        let r = Ray::new(origin, direction);
+        quad.hit(
+            &r,
+            Interval::new(0.001, f64::INFINITY),
+            &mut rec,
+            &mut IndependentSampler::new(0),
+        )
+        .then_some(rec)
+    }
+
+    #[test]
+    fn test_hit() {
+        let mut sampler = IndependentSampler::new(0);
+        let quad = unit_square();
+        let origin = Point3::new(0.0, 0.0, 0.0);
+
+        let rec = hit(&quad, origin.clone(), Vec3::new(0.2, -0.3, -1.0)).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!((rec.u - 0.7).abs() < 1e-12 && (rec.v - 0.2).abs() < 1e-12);
+        assert!((&rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-12);
+        assert!(rec.front_face);
+
+        // The edges belong to the quad, points just outside them don't
+        let rec = hit(&quad, origin.clone(), Vec3::new(0.5, -0.5, -1.0)).unwrap();
+        assert!((rec.u - 1.0).abs() < 1e-12 && rec.v.abs() < 1e-12);
+        assert!(hit(&quad, origin.clone(), Vec3::new(0.5 + 1e-9, 0.0, -1.0)).is_none());
+        assert!(hit(&quad, origin.clone(), Vec3::new(0.0, -0.5 - 1e-9, -1.0)).is_none());
+
+        // Parallel to the plane, and pointing away from it
+        assert!(hit(&quad, Point3::new(0.0, 0.0, -1.0), Vec3::new(1.0, 0.0, 0.0)).is_none());
+        assert!(hit(&quad, origin.clone(), Vec3::new(0.0, 0.0, 1.0)).is_none());
+
+        // A hit at an end of the ray interval doesn't count
+        let r = Ray::new(origin, Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        assert!(!quad.hit(&r, Interval::new(0.001, 1.0), &mut rec, &mut sampler));
+        assert!(quad.hit(&r, Interval::new(0.001, 1.0 + 1e-9), &mut rec, &mut sampler));
+    }
+
+    #[test]
+    fn test_pdf() {
+        let quad = unit_square();
+        let origin = Point3::new(0.3, 0.1, 1.0);
+        let direction = Vec3::new(-0.5, 0.2, -2.0);
+
+        // Hits the plane at distance 2 along the direction, at an angle with the normal
+        let distance_squared = direction.length_squared();
+        let cosine = 2.0 / direction.length();
+        let expected = distance_squared / (cosine * 1.0);
+        assert!((quad.pdf_value(&origin, &direction) - expected).abs() < 1e-12);
+        assert!((quad.pdf_value(&origin, &(3.0 * direction)) - expected).abs() < 1e-12);
+        assert_eq!(quad.pdf_value(&origin, &Vec3::new(2.0, 0.0, -1.0)), 0.0);
+
+        let mut sampler = IndependentSampler::new(4);
+        for _ in 0..100 {
+            let direction = quad.random(&origin, &mut sampler);
+            let rec = hit(&quad, origin.clone(), direction).unwrap();
+            assert!((rec.t - 1.0).abs() < 1e-9);
+        }
+    }
+}
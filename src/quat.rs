@@ -0,0 +1,195 @@
+use crate::{Mat4, Vec3, utils::degrees_to_radians};
+use std::ops::Mul;
+
+/// Quaternion `w + xi + yj + zk`, used as a unit quaternion to represent rotations
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quat {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quat {
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Quat { w, x, y, z }
+    }
+
+    pub fn identity() -> Self {
+        Quat::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    /// Rotation by `degrees` around `axis`, with the same direction as `Mat4::rotation`
+    pub fn from_axis_angle(axis: &Vec3, degrees: f64) -> Self {
+        let a = axis.unit_vector();
+        let (sin, cos) = (degrees_to_radians(degrees) / 2.0).sin_cos();
+        Quat::new(cos, sin * a.x(), sin * a.y(), sin * a.z())
+    }
+
+    pub fn dot(&self, other: &Quat) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn length(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Self {
+        let inv_length = 1.0 / self.length();
+        Quat::new(
+            self.w * inv_length,
+            self.x * inv_length,
+            self.y * inv_length,
+            self.z * inv_length,
+        )
+    }
+
+    /// Inverse of a unit quaternion
+    pub fn conjugate(&self) -> Self {
+        Quat::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// Rotate `v` by this unit quaternion
+    pub fn rotate(&self, v: &Vec3) -> Vec3 {
+        let p = Quat::new(0.0, v.x(), v.y(), v.z());
+        let rotated = &(self * &p) * &self.conjugate();
+        Vec3::new(rotated.x, rotated.y, rotated.z)
+    }
+
+    /// Spherical linear interpolation between the rotations `a` (at `t` = 0) and `b` (at `t` = 1),
+    /// turning at constant speed along the shortest path
+    pub fn slerp(a: &Quat, b: &Quat, t: f64) -> Self {
+        // q and -q are the same rotation; pick the one on the same side as `a`
+        let mut cos_theta = a.dot(b);
+        let b = if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            Quat::new(-b.w, -b.x, -b.y, -b.z)
+        } else {
+            b.clone()
+        };
+
+        // Nearly identical rotations would divide by almost zero, so interpolate linearly
+        let (weight_a, weight_b) = if cos_theta > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((1.0 - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+
+        Quat::new(
+            weight_a * a.w + weight_b * b.w,
+            weight_a * a.x + weight_b * b.x,
+            weight_a * a.y + weight_b * b.y,
+            weight_a * a.z + weight_b * b.z,
+        )
+        .normalize()
+    }
+
+    /// Rotation matrix of this unit quaternion
+    pub fn to_mat4(&self) -> Mat4 {
+        let Quat { w, x, y, z } = *self;
+        Mat4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::identity()
+    }
+}
+
+/// Hamilton product: `a * b` rotates by `b` first, then by `a`
+impl Mul<&Quat> for &Quat {
+    type Output = Quat;
+
+    fn mul(self, other: &Quat) -> Self::Output {
+        Quat::new(
+            self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        )
+    }
+}
+
+impl Mul<Quat> for Quat {
+    type Output = Quat;
+
+    fn mul(self, other: Quat) -> Self::Output {
+        &self * &other
+    }
+}
+
+#[cfg(test)]
+mod quat_tests {
+    use super::*;
+
+    fn assert_close(a: &Vec3, b: &Vec3) {
+        assert!((a - b.clone()).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_rotation_matches_matrix() {
+        let axis = Vec3::new(1.0, -2.0, 0.5);
+        let q = Quat::from_axis_angle(&axis, 70.0);
+        let m = Mat4::rotation(&axis, 70.0);
+        let v = Vec3::new(0.3, 0.4, -1.2);
+
+        assert_close(&q.rotate(&v), &m.transform_vector(&v));
+        assert_close(&q.to_mat4().transform_vector(&v), &m.transform_vector(&v));
+        assert_close(&q.conjugate().rotate(&q.rotate(&v)), &v);
+
+        // Composing two rotations around the same axis adds their angles
+        let q2 = &Quat::from_axis_angle(&axis, 20.0) * &q;
+        assert_close(
+            &q2.rotate(&v),
+            &Quat::from_axis_angle(&axis, 90.0).rotate(&v),
+        );
+    }
+
+    #[test]
+    fn test_slerp() {
+        let z = Vec3::new(0.0, 0.0, 1.0);
+        let a = Quat::identity();
+        let b = Quat::from_axis_angle(&z, 90.0);
+        let x = Vec3::new(1.0, 0.0, 0.0);
+
+        assert_close(&Quat::slerp(&a, &b, 0.0).rotate(&x), &x);
+        assert_close(&Quat::slerp(&a, &b, 1.0).rotate(&x), &b.rotate(&x));
+        // Constant angular speed: a third of the way is a 30 degrees turn
+        assert_close(
+            &Quat::slerp(&a, &b, 1.0 / 3.0).rotate(&x),
+            &Quat::from_axis_angle(&z, 30.0).rotate(&x),
+        );
+        // -b is the same rotation as b, and slerp still takes the short way
+        let minus_b = Quat::new(-b.w, -b.x, -b.y, -b.z);
+        assert_close(
+            &Quat::slerp(&a, &minus_b, 0.5).rotate(&x),
+            &Quat::from_axis_angle(&z, 45.0).rotate(&x),
+        );
+    }
+}
@@ -1,16 +1,25 @@
 use crate::point::Point3;
 use crate::vec::Vec3;
 
-/// Represents a ray using `origin` and `direction`
+/// Represents a ray using `origin` and `direction`, cast at the instant `time`
 #[derive(Clone, Default)]
 pub struct Ray {
     origin: Point3,
     direction: Vec3,
+    time: f64,
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Self {
-        Ray { origin, direction }
+        Ray::with_time(origin, direction, 0.0)
+    }
+
+    pub fn with_time(origin: Point3, direction: Vec3, time: f64) -> Self {
+        Ray {
+            origin,
+            direction,
+            time,
+        }
     }
 
     /// Get value along a ray at time `t`
@@ -25,4 +34,8 @@ impl Ray {
     pub fn direction(&self) -> &Vec3 {
         &self.direction
     }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
 }
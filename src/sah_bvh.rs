@@ -0,0 +1,396 @@
+use crate::{Aabb, HitRecord, Hittable, HittableList, Interval, Point3, Ray, Sampler};
+use std::sync::Arc;
+
+// Number of buckets the centroids are binned into when looking for the best split
+const BIN_COUNT: usize = 12;
+// Leaves are only created with atmost these many objects, unless the objects can't be split
+const MAX_LEAF_SIZE: usize = 4;
+// Cost of visiting an interior node relative to the cost of intersecting an object
+const TRAVERSAL_COST: f64 = 0.125;
+
+/// Statistics collected while building a `SahBvh`
+#[derive(Clone, Debug, Default)]
+pub struct BvhStats {
+    /// Count of interior and leaf nodes
+    pub node_count: usize,
+    /// Count of leaf nodes
+    pub leaf_count: usize,
+    /// Count of objects stored in all the leaves
+    pub object_count: usize,
+    /// Depth of the deepest leaf, the root is at depth 0
+    pub max_depth: usize,
+    /// Fewest objects stored in a leaf
+    pub min_leaf_size: usize,
+    /// Most objects stored in a leaf
+    pub max_leaf_size: usize,
+    /// Average count of objects stored in a leaf
+    pub mean_leaf_size: f64,
+}
+
+// Node of the flattened tree
+//
+// The nodes are stored in depth first order, so the first child of an interior node is
+// always the node right after it
+struct LinearNode {
+    bbox: Aabb,
+    // Index of the first object for a leaf, or index of the second child for an interior node
+    offset: usize,
+    // Count of objects in a leaf, 0 for an interior node
+    count: usize,
+    // Axis along which the children of an interior node were split
+    axis: usize,
+}
+
+// Object waiting to be placed in the tree along with its box
+struct BuildObject {
+    object: Arc<dyn Hittable>,
+    bbox: Aabb,
+    centroid: Point3,
+}
+
+#[derive(Clone, Default)]
+struct Bin {
+    count: usize,
+    bbox: Aabb,
+}
+
+/// Bounding volume hierarchy built using the surface area heuristic (SAH)
+///
+/// At each node the object centroids are binned along the axis of largest spread and the
+/// split with the lowest expected intersection cost is chosen. The tree is then stored as a
+/// contiguous array of nodes which is traversed using an explicit stack.
+pub struct SahBvh {
+    nodes: Vec<LinearNode>,
+    objects: Vec<Arc<dyn Hittable>>,
+    stats: BvhStats,
+}
+
+impl SahBvh {
+    /// Build the hierarchy over all the objects of the `list`
+    pub fn from_list(list: HittableList) -> Self {
+        SahBvh::new(list.objects)
+    }
+
+    /// Build the hierarchy over the `objects`
+    pub fn new(objects: Vec<Arc<dyn Hittable>>) -> Self {
+        let mut build_objects: Vec<BuildObject> = objects
+            .into_iter()
+            .map(|object| {
+                let bbox = object.bounding_box();
+                let centroid = bbox.centroid();
+                BuildObject {
+                    object,
+                    bbox,
+                    centroid,
+                }
+            })
+            .collect();
+
+        let mut bvh = SahBvh {
+            nodes: Vec::new(),
+            objects: Vec::with_capacity(build_objects.len()),
+            stats: BvhStats {
+                min_leaf_size: usize::MAX,
+                ..Default::default()
+            },
+        };
+        if !build_objects.is_empty() {
+            bvh.build(&mut build_objects, 0);
+        }
+
+        bvh.stats.node_count = bvh.nodes.len();
+        if bvh.stats.leaf_count > 0 {
+            bvh.stats.mean_leaf_size = bvh.stats.object_count as f64 / bvh.stats.leaf_count as f64;
+        } else {
+            bvh.stats.min_leaf_size = 0;
+        }
+        bvh
+    }
+
+    /// Statistics about the shape of the built tree
+    pub fn stats(&self) -> &BvhStats {
+        &self.stats
+    }
+
+    // Recursively build the subtree over `build_objects` and return the index of its root
+    fn build(&mut self, build_objects: &mut [BuildObject], depth: usize) -> usize {
+        let node_index = self.nodes.len();
+
+        // The centroid bounds are kept as plain coordinates, since `Aabb` pads thin boxes and
+        // would hide centroids which all lie at the same place
+        let mut bbox = Aabb::empty();
+        let mut centroid_min = [f64::INFINITY; 3];
+        let mut centroid_max = [f64::NEG_INFINITY; 3];
+        for build_object in build_objects.iter() {
+            bbox = Aabb::surrounding(&bbox, &build_object.bbox);
+            for axis in 0..3 {
+                centroid_min[axis] = centroid_min[axis].min(build_object.centroid[axis]);
+                centroid_max[axis] = centroid_max[axis].max(build_object.centroid[axis]);
+            }
+        }
+        self.nodes.push(LinearNode {
+            bbox: bbox.clone(),
+            offset: 0,
+            count: 0,
+            axis: 0,
+        });
+
+        let count = build_objects.len();
+        let extent = |axis: usize| centroid_max[axis] - centroid_min[axis];
+        let axis = (0..3)
+            .max_by(|&a, &b| extent(a).total_cmp(&extent(b)))
+            .unwrap();
+        let (axis_min, axis_size) = (centroid_min[axis], extent(axis));
+
+        // All centroids are at the same place, so no split can separate the objects
+        if count == 1 || axis_size <= 0.0 {
+            self.make_leaf(node_index, build_objects, depth);
+            return node_index;
+        }
+
+        // Bin the objects by their centroids
+        let bin_index = |build_object: &BuildObject| {
+            let offset = (build_object.centroid[axis] - axis_min) / axis_size;
+            ((offset * BIN_COUNT as f64) as usize).min(BIN_COUNT - 1)
+        };
+        let mut bins = vec![Bin::default(); BIN_COUNT];
+        for build_object in build_objects.iter() {
+            let bin = &mut bins[bin_index(build_object)];
+            bin.count += 1;
+            bin.bbox = Aabb::surrounding(&bin.bbox, &build_object.bbox);
+        }
+
+        // Sweep from both the ends to find the cost of splitting after each bin
+        let mut costs = [0.0; BIN_COUNT - 1];
+        let mut below = Bin::default();
+        for (split, bin) in bins.iter().take(BIN_COUNT - 1).enumerate() {
+            below.count += bin.count;
+            below.bbox = Aabb::surrounding(&below.bbox, &bin.bbox);
+            costs[split] = below.count as f64 * below.bbox.surface_area();
+        }
+        let mut above = Bin::default();
+        for split in (0..BIN_COUNT - 1).rev() {
+            let bin = &bins[split + 1];
+            above.count += bin.count;
+            above.bbox = Aabb::surrounding(&above.bbox, &bin.bbox);
+            costs[split] += above.count as f64 * above.bbox.surface_area();
+        }
+
+        let (best_split, best_cost) = costs
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(split, cost)| (split, *cost))
+            .unwrap();
+        let best_cost = TRAVERSAL_COST + best_cost / bbox.surface_area();
+        let leaf_cost = count as f64;
+
+        if count <= MAX_LEAF_SIZE && leaf_cost <= best_cost {
+            self.make_leaf(node_index, build_objects, depth);
+            return node_index;
+        }
+
+        // Move the objects below the split to the front
+        let mut mid = 0;
+        for i in 0..count {
+            if bin_index(&build_objects[i]) <= best_split {
+                build_objects.swap(i, mid);
+                mid += 1;
+            }
+        }
+        // All the objects landed on one side, fall back to splitting them in half
+        if mid == 0 || mid == count {
+            mid = count / 2;
+            build_objects.sort_by(|a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+        }
+
+        let (left_objects, right_objects) = build_objects.split_at_mut(mid);
+        self.build(left_objects, depth + 1);
+        let second_child = self.build(right_objects, depth + 1);
+
+        let node = &mut self.nodes[node_index];
+        node.offset = second_child;
+        node.axis = axis;
+        node_index
+    }
+
+    fn make_leaf(&mut self, node_index: usize, build_objects: &[BuildObject], depth: usize) {
+        let node = &mut self.nodes[node_index];
+        node.offset = self.objects.len();
+        node.count = build_objects.len();
+        self.objects.extend(
+            build_objects
+                .iter()
+                .map(|build_object| build_object.object.clone()),
+        );
+
+        self.stats.leaf_count += 1;
+        self.stats.object_count += build_objects.len();
+        self.stats.max_depth = self.stats.max_depth.max(depth);
+        self.stats.min_leaf_size = self.stats.min_leaf_size.min(build_objects.len());
+        self.stats.max_leaf_size = self.stats.max_leaf_size.max(build_objects.len());
+    }
+}
+
+impl Hittable for SahBvh {
+    /// Find the closest intersection by walking the nodes whose boxes are hit by the ray,
+    /// visiting the nearer child first so that farther nodes can be skipped
+    fn hit(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let mut hit_anything = false;
+        let mut closest_so_far = ray_t.max;
+        let mut to_visit: Vec<usize> = Vec::with_capacity(64);
+        let mut node_index = 0;
+
+        loop {
+            let node = &self.nodes[node_index];
+            if node.bbox.hit(r, Interval::new(ray_t.min, closest_so_far)) {
+                if node.count > 0 {
+                    for object in &self.objects[node.offset..node.offset + node.count] {
+                        if object.hit(r, Interval::new(ray_t.min, closest_so_far), rec, sampler) {
+                            hit_anything = true;
+                            closest_so_far = rec.t;
+                        }
+                    }
+                } else if r.direction()[node.axis] < 0.0 {
+                    // The ray travels towards the lower side, so visit the second child first
+                    to_visit.push(node_index + 1);
+                    node_index = node.offset;
+                    continue;
+                } else {
+                    to_visit.push(node.offset);
+                    node_index += 1;
+                    continue;
+                }
+            }
+
+            match to_visit.pop() {
+                Some(next) => node_index = next,
+                None => break,
+            }
+        }
+
+        hit_anything
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        match self.nodes.first() {
+            Some(root) => root.bbox.clone(),
+            None => Aabb::empty(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod sah_bvh_tests {
+    use super::*;
+    use crate::{IndependentSampler, Lambertian, Material, Quad, Sphere, Vec3};
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+    fn random_point(rng: &mut SmallRng, size: f64) -> Point3 {
+        Point3::new(
+            rng.random_range(-size..size),
+            rng.random_range(-size..size),
+            rng.random_range(-size..size),
+        )
+    }
+
+    fn random_objects(rng: &mut SmallRng, count: usize) -> Vec<Arc<dyn Hittable>> {
+        let mat: Arc<dyn Material> = Arc::new(Lambertian::default());
+        (0..count)
+            .map(|i| -> Arc<dyn Hittable> {
+                let center = random_point(rng, 10.0);
+                if i % 4 == 0 {
+                    // Flat objects get padded boxes
+                    Arc::new(Quad::new(
+                        center,
+                        Vec3::new(rng.random_range(0.1..2.0), 0.0, 0.0),
+                        Vec3::new(0.0, 0.0, rng.random_range(0.1..2.0)),
+                        mat.clone(),
+                    ))
+                } else {
+                    Arc::new(Sphere::new(center, rng.random_range(0.1..1.5), mat.clone()))
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hit_matches_linear_scan() {
+        let mut sampler = IndependentSampler::new(0);
+        let mut rng = SmallRng::seed_from_u64(11);
+        for count in [1, 2, 3, 10, 200] {
+            let objects = random_objects(&mut rng, count);
+            let mut list = HittableList::default();
+            for object in &objects {
+                list.add(object.clone());
+            }
+            let bvh = SahBvh::new(objects);
+
+            for _ in 0..300 {
+                let r = Ray::new(
+                    random_point(&mut rng, 15.0),
+                    random_point(&mut rng, 1.0).as_vec3().clone(),
+                );
+                let interval = Interval::new(0.001, f64::INFINITY);
+                let (mut expected, mut actual) = (HitRecord::default(), HitRecord::default());
+                let hit = list.hit(&r, interval.clone(), &mut expected, &mut sampler);
+                assert_eq!(bvh.hit(&r, interval, &mut actual, &mut sampler), hit);
+                if hit {
+                    assert_eq!(actual.t, expected.t);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_stats_count_every_object_once() {
+        let mut rng = SmallRng::seed_from_u64(5);
+        let objects = random_objects(&mut rng, 500);
+        let bvh = SahBvh::new(objects.clone());
+
+        let stats = bvh.stats();
+        assert_eq!(stats.object_count, 500);
+        assert!(stats.min_leaf_size >= 1 && stats.max_leaf_size <= stats.object_count);
+        assert!((stats.mean_leaf_size * stats.leaf_count as f64 - 500.0).abs() < 1e-9);
+        assert_eq!(stats.node_count, 2 * stats.leaf_count - 1);
+        for object in &objects {
+            let copies = bvh
+                .objects
+                .iter()
+                .filter(|o| Arc::ptr_eq(o, object))
+                .count();
+            assert_eq!(copies, 1);
+        }
+
+        // Objects sharing their centroid can't be separated, so they end up in a single leaf
+        let mat: Arc<dyn Material> = Arc::new(Lambertian::default());
+        let coincident: Vec<Arc<dyn Hittable>> = (1..=20)
+            .map(|i| -> Arc<dyn Hittable> {
+                Arc::new(Sphere::new(
+                    Point3::new(1.0, 2.0, 3.0),
+                    i as f64,
+                    mat.clone(),
+                ))
+            })
+            .collect();
+        let stats = SahBvh::new(coincident).stats().clone();
+        assert_eq!(
+            (stats.node_count, stats.max_depth, stats.object_count),
+            (1, 0, 20)
+        );
+
+        let empty = SahBvh::new(Vec::new());
+        assert_eq!(empty.stats().object_count, 0);
+        assert_eq!(empty.stats().min_leaf_size, 0);
+    }
+}
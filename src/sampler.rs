@@ -0,0 +1,562 @@
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+/// Source of the random numbers used while rendering
+///
+/// The camera restarts the sampler for every sample of every pixel, so the numbers drawn
+/// for a sample only depend on the seed, the pixel and the sample index
+pub trait Sampler {
+    /// Restart the sequence for the `sample_index`th sample of the pixel (i, j)
+    fn start_pixel_sample(&mut self, i: usize, j: usize, sample_index: usize);
+
+    /// Generate the next random number in the range [0, 1)
+    fn get_1d(&mut self) -> f64;
+
+    /// Generate the next pair of random numbers in the range [0, 1)
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+
+    /// Generate the next random number in the range [min, max)
+    fn random_f64(&mut self, min: f64, max: f64) -> f64 {
+        min + (max - min) * self.get_1d()
+    }
+}
+
+/// Kinds of samplers the camera can use
+#[derive(Clone, Copy, Default)]
+pub enum SamplerType {
+    /// Independent uniform random numbers
+    #[default]
+    Independent,
+    /// Jittered points from a grid of strata, the strata are shuffled between dimensions
+    Stratified,
+    /// Owen scrambled Halton sequence
+    Halton,
+    /// Owen scrambled Sobol sequence, with every pair of dimensions shuffled independently
+    Sobol,
+}
+
+impl SamplerType {
+    /// Create a sampler of this type for rendering `samples_per_pixel` samples in every pixel
+    pub fn make_sampler(&self, samples_per_pixel: usize, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerType::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerType::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            SamplerType::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerType::Sobol => Box::new(SobolSampler::new(samples_per_pixel, seed)),
+        }
+    }
+}
+
+/// Sampler which draws independent uniform random numbers
+pub struct IndependentSampler {
+    seed: u64,
+    rng: SmallRng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        IndependentSampler {
+            seed,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, i: usize, j: usize, sample_index: usize) {
+        let seed = hash(&[self.seed, i as u64, j as u64, sample_index as u64]);
+        self.rng = SmallRng::seed_from_u64(seed);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.random()
+    }
+}
+
+/// Sampler which splits every dimension into `samples_per_pixel` strata and places each
+/// sample at a random position within its own stratum
+///
+/// Pairs of dimensions are stratified together over a grid. The order in which samples visit
+/// the strata is shuffled separately for every dimension, so the dimensions are uncorrelated.
+pub struct StratifiedSampler {
+    samples_per_pixel: usize,
+    // Grid size used for stratifying pairs of dimensions
+    x_strata: usize,
+    y_strata: usize,
+    seed: u64,
+    pixel: (usize, usize),
+    sample_index: usize,
+    dimension: usize,
+    rng: SmallRng,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        let x_strata = (samples_per_pixel as f64).sqrt().ceil() as usize;
+        let y_strata = samples_per_pixel.div_ceil(x_strata);
+        StratifiedSampler {
+            samples_per_pixel,
+            x_strata,
+            y_strata,
+            seed,
+            pixel: (0, 0),
+            sample_index: 0,
+            dimension: 0,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+
+    // Hash used for shuffling the strata of the current dimension
+    fn dimension_hash(&self) -> u64 {
+        hash(&[
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            self.dimension as u64,
+            self.seed,
+        ])
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, i: usize, j: usize, sample_index: usize) {
+        self.pixel = (i, j);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+        let seed = hash(&[i as u64, j as u64, sample_index as u64, self.seed]);
+        self.rng = SmallRng::seed_from_u64(seed);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let stratum = permutation_element(
+            self.sample_index % self.samples_per_pixel,
+            self.samples_per_pixel,
+            self.dimension_hash(),
+        );
+        self.dimension += 1;
+        let delta: f64 = self.rng.random();
+        (stratum as f64 + delta) / self.samples_per_pixel as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        // The grid can have more strata than samples, some of them are then left empty
+        let stratum = permutation_element(
+            self.sample_index % self.samples_per_pixel,
+            self.x_strata * self.y_strata,
+            self.dimension_hash(),
+        );
+        self.dimension += 2;
+        let x = stratum % self.x_strata;
+        let y = stratum / self.x_strata;
+        let (dx, dy): (f64, f64) = (self.rng.random(), self.rng.random());
+        (
+            (x as f64 + dx) / self.x_strata as f64,
+            (y as f64 + dy) / self.y_strata as f64,
+        )
+    }
+}
+
+// Bases of the Halton sequence, one prime per dimension
+const PRIMES: [u64; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// Sampler which generates the Halton sequence, a low discrepancy sequence using the radical
+/// inverse in a different prime base for every dimension
+///
+/// Every pixel uses its own Owen scrambling of the sequence, so neighbouring pixels don't
+/// share the same sample pattern. Dimensions past the last supported prime use independent
+/// random numbers.
+pub struct HaltonSampler {
+    seed: u64,
+    pixel: (usize, usize),
+    sample_index: usize,
+    dimension: usize,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        HaltonSampler {
+            seed,
+            pixel: (0, 0),
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, i: usize, j: usize, sample_index: usize) {
+        self.pixel = (i, j);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension_hash = hash(&[
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            self.dimension as u64,
+            self.seed,
+        ]);
+        let value = if self.dimension < PRIMES.len() {
+            owen_scrambled_radical_inverse(
+                self.sample_index as u64,
+                PRIMES[self.dimension],
+                dimension_hash,
+            )
+        } else {
+            to_unit_f64(hash(&[dimension_hash, self.sample_index as u64]))
+        };
+        self.dimension += 1;
+        value
+    }
+}
+
+/// Sampler which generates the Sobol sequence, a low discrepancy sequence in base 2
+///
+/// Only the first two Sobol dimensions are used; every further pair of dimensions reuses them
+/// with their own Owen scrambling and their own shuffling of the sample order ("padding").
+/// This keeps the good 2D distribution in every pair without needing tables for hundreds of
+/// Sobol dimensions.
+pub struct SobolSampler {
+    samples_per_pixel: usize,
+    seed: u64,
+    pixel: (usize, usize),
+    sample_index: usize,
+    dimension: usize,
+}
+
+impl SobolSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> Self {
+        SobolSampler {
+            samples_per_pixel: samples_per_pixel.max(1),
+            seed,
+            pixel: (0, 0),
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    // Shuffled sample index and scrambling hash for the current dimension
+    fn index_and_hash(&self) -> (u32, u64) {
+        let dimension_hash = hash(&[
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            self.dimension as u64,
+            self.seed,
+        ]);
+        let index = permutation_element(
+            self.sample_index % self.samples_per_pixel,
+            self.samples_per_pixel,
+            dimension_hash,
+        );
+        (index as u32, dimension_hash)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, i: usize, j: usize, sample_index: usize) {
+        self.pixel = (i, j);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (index, dimension_hash) = self.index_and_hash();
+        self.dimension += 1;
+        sobol_sample(index, 0, dimension_hash as u32)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (index, dimension_hash) = self.index_and_hash();
+        self.dimension += 2;
+        (
+            sobol_sample(index, 0, dimension_hash as u32),
+            sobol_sample(index, 1, (dimension_hash >> 32) as u32),
+        )
+    }
+}
+
+// Largest f64 below 1
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+// Map the 53 high bits of `v` to [0, 1)
+fn to_unit_f64(v: u64) -> f64 {
+    (v >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+
+// Reverse the digits of `a` in `base` around the decimal point, while randomly permuting each
+// digit depending on the digits before it (Owen scrambling)
+fn owen_scrambled_radical_inverse(mut a: u64, base: u64, seed: u64) -> f64 {
+    let limit = u64::MAX / base - base;
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut reversed_digits = 0;
+
+    // Keep going after `a` runs out of digits, as the scrambled zero digits aren't zero
+    while 1.0 - (base - 1) as f64 * inv_base_m < 1.0 && reversed_digits < limit {
+        let next = a / base;
+        let digit_value = a - next * base;
+        let digit_hash = mix_bits(seed ^ reversed_digits);
+        let digit_value = permutation_element(digit_value as usize, base as usize, digit_hash);
+        reversed_digits = reversed_digits * base + digit_value as u64;
+        inv_base_m *= inv_base;
+        a = next;
+    }
+    (inv_base_m * reversed_digits as f64).min(ONE_MINUS_EPSILON)
+}
+
+// Sample `a` of the Owen scrambled Sobol sequence in the first (0) or second (1) dimension
+fn sobol_sample(a: u32, dimension: usize, seed: u32) -> f64 {
+    let v = owen_scramble(sobol_bits(a, dimension), seed);
+    (v as f64 / (1u64 << 32) as f64).min(ONE_MINUS_EPSILON)
+}
+
+// Sample `a` of the Sobol sequence as a 32 bit fraction, before scrambling
+fn sobol_bits(mut a: u32, dimension: usize) -> u32 {
+    let mut v = 0u32;
+    let mut column_index = 0;
+    while a != 0 {
+        if a & 1 != 0 {
+            v ^= sobol_column(dimension, column_index);
+        }
+        a >>= 1;
+        column_index += 1;
+    }
+    v
+}
+
+// `k`th column of the generator matrix of the first two Sobol dimensions
+//
+// The first dimension is the van der Corput sequence. The direction numbers of the second
+// dimension follow from the primitive polynomial x + 1, i.e. m_k = 2 m_(k-1) xor m_(k-1)
+fn sobol_column(dimension: usize, k: usize) -> u32 {
+    if dimension == 0 {
+        return 1 << (31 - k);
+    }
+    let mut m: u32 = 1;
+    for _ in 0..k {
+        m ^= m << 1;
+    }
+    m << (31 - k)
+}
+
+// Randomly flip the bits of `v`, where the flip of each bit depends on all the higher bits,
+// which preserves the stratification of base 2 sequences
+fn owen_scramble(mut v: u32, seed: u32) -> u32 {
+    if seed & 1 != 0 {
+        v ^= 1 << 31;
+    }
+    for b in 1..32 {
+        let mask = u32::MAX << (32 - b);
+        if (mix_bits(((v & mask) ^ seed) as u64) as u32) & (1 << b) != 0 {
+            v ^= 1 << (31 - b);
+        }
+    }
+    v
+}
+
+// Element `i` of a random permutation of [0, n) chosen by `seed`, without building the
+// permutation (Kensler's hash based permutation)
+fn permutation_element(i: usize, n: usize, seed: u64) -> usize {
+    let n = n as u32;
+    let p = seed as u32;
+    let mut i = i as u32;
+    let mut w = n.wrapping_sub(1);
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+    (i.wrapping_add(p) % n) as usize
+}
+
+/// Hash the `values` into a well mixed 64 bit number
+pub(crate) fn hash(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0x243f_6a88_85a3_08d3, |h, &value| mix_bits(h ^ value))
+}
+
+// Finalizer of the SplitMix64 generator; flips about half the output bits for every input bit
+fn mix_bits(mut v: u64) -> u64 {
+    v = v.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    v = (v ^ (v >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    v = (v ^ (v >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    v ^ (v >> 31)
+}
+
+#[cfg(test)]
+mod sampler_tests {
+    use super::*;
+
+    fn samplers(samples_per_pixel: usize, seed: u64) -> Vec<Box<dyn Sampler>> {
+        [
+            SamplerType::Independent,
+            SamplerType::Stratified,
+            SamplerType::Halton,
+            SamplerType::Sobol,
+        ]
+        .iter()
+        .map(|sampler_type| sampler_type.make_sampler(samples_per_pixel, seed))
+        .collect()
+    }
+
+    // The first `dimensions` numbers of every sample of the pixel (i, j)
+    fn pixel_samples(
+        sampler: &mut dyn Sampler,
+        (i, j): (usize, usize),
+        samples_per_pixel: usize,
+        dimensions: usize,
+    ) -> Vec<Vec<f64>> {
+        (0..samples_per_pixel)
+            .map(|sample_index| {
+                sampler.start_pixel_sample(i, j, sample_index);
+                (0..dimensions).map(|_| sampler.get_1d()).collect()
+            })
+            .collect()
+    }
+
+    // Whether every interval [k / n, (k + 1) / n) holds exactly one of the `values`
+    fn one_per_stratum(values: impl Iterator<Item = f64>, n: usize) -> bool {
+        let mut counts = vec![0; n];
+        for value in values {
+            counts[(value * n as f64) as usize] += 1;
+        }
+        counts.iter().all(|&count| count == 1)
+    }
+
+    #[test]
+    fn test_samples_are_in_unit_interval() {
+        for mut sampler in samplers(13, 3) {
+            for pixel in [(0, 0), (7, 3), (1000, 999)] {
+                for sample in pixel_samples(sampler.as_mut(), pixel, 13, 80) {
+                    assert!(sample.iter().all(|value| (0.0..1.0).contains(value)));
+                }
+                sampler.start_pixel_sample(pixel.0, pixel.1, 5);
+                for _ in 0..40 {
+                    let (x, y) = sampler.get_2d();
+                    assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+                }
+            }
+        }
+
+        // The largest scrambled values are rounded below 1
+        assert!(owen_scrambled_radical_inverse(u64::MAX, 311, u64::MAX) < 1.0);
+        assert!(sobol_sample(u32::MAX, 1, u32::MAX) < 1.0);
+    }
+
+    #[test]
+    fn test_stratified_sampler_fills_every_stratum() {
+        let n = 4;
+        let mut sampler = StratifiedSampler::new(n * n, 9);
+        for pixel in [(0, 0), (12, 5)] {
+            for dimension in 0..3 {
+                let mut cells = vec![0; n * n];
+                let mut values = Vec::new();
+                for sample_index in 0..n * n {
+                    sampler.start_pixel_sample(pixel.0, pixel.1, sample_index);
+                    for _ in 0..dimension {
+                        sampler.get_2d();
+                    }
+                    let (x, y) = sampler.get_2d();
+                    cells[(y * n as f64) as usize * n + (x * n as f64) as usize] += 1;
+                    values.push(sampler.get_1d());
+                }
+                assert!(cells.iter().all(|&count| count == 1));
+                assert!(one_per_stratum(values.into_iter(), n * n));
+            }
+        }
+    }
+
+    #[test]
+    fn test_low_discrepancy_points() {
+        // First points of the first two Sobol dimensions, in index order rather than Gray code
+        // order
+        let expected = [
+            [0.0, 0.5, 0.25, 0.75, 0.125, 0.625, 0.375, 0.875],
+            [0.0, 0.5, 0.75, 0.25, 0.625, 0.125, 0.375, 0.875],
+        ];
+        for (dimension, expected) in expected.iter().enumerate() {
+            for (a, &value) in expected.iter().enumerate() {
+                let actual = sobol_bits(a as u32, dimension) as f64 / (1u64 << 32) as f64;
+                assert_eq!(actual, value);
+            }
+        }
+
+        // Owen scrambling keeps the stratification of the first points: every elementary
+        // interval of the Sobol pairs, and every interval of base^k in a Halton dimension,
+        // holds exactly one point
+        let mut sobol = SobolSampler::new(16, 1);
+        let samples: Vec<_> = (0..16)
+            .map(|sample_index| {
+                sobol.start_pixel_sample(2, 3, sample_index);
+                [sobol.get_2d(), sobol.get_2d()]
+            })
+            .collect();
+        for pair in 0..2 {
+            for x_strata in [1, 2, 4, 8, 16] {
+                let y_strata = 16 / x_strata;
+                let mut cells = [0; 16];
+                for sample in &samples {
+                    let (x, y) = sample[pair];
+                    let x = (x * x_strata as f64) as usize;
+                    let y = (y * y_strata as f64) as usize;
+                    cells[y * x_strata + x] += 1;
+                }
+                assert!(cells.iter().all(|&count| count == 1));
+            }
+        }
+
+        let mut halton = HaltonSampler::new(1);
+        let samples = pixel_samples(&mut halton, (2, 3), 125, 3);
+        for (dimension, (base, count)) in [(2, 64), (3, 81), (5, 125)].into_iter().enumerate() {
+            for strata in [base, count] {
+                let values = samples[..strata].iter().map(|sample| sample[dimension]);
+                assert!(one_per_stratum(values, strata));
+            }
+        }
+    }
+
+    #[test]
+    fn test_seed_decides_the_sequence() {
+        let same = samplers(16, 42);
+        let different = samplers(16, 43);
+        for ((mut a, mut b), mut c) in samplers(16, 42).into_iter().zip(same).zip(different) {
+            let first = pixel_samples(a.as_mut(), (4, 2), 16, 6);
+            assert_eq!(first, pixel_samples(b.as_mut(), (4, 2), 16, 6));
+            assert_ne!(first, pixel_samples(c.as_mut(), (4, 2), 16, 6));
+            // Other pixels get other samples too
+            assert_ne!(first, pixel_samples(a.as_mut(), (5, 2), 16, 6));
+        }
+    }
+}
@@ -1,28 +1,83 @@
-use std::rc::Rc;
+use std::f64::consts::PI;
+use std::sync::Arc;
 
-use crate::{HitRecord, Interval, Material, Point3, Ray, hittable::Hittable};
+use crate::{
+    Aabb, HitRecord, Interval, Material, Onb, Point3, Ray, Sampler, Vec3, hittable::Hittable,
+};
 
 pub struct Sphere {
-    center: Point3,
+    // Center at time 0 as the origin, and the distance moved until time 1 as the direction
+    center: Ray,
     radius: f64,
-    mat: Rc<dyn Material>,
+    mat: Arc<dyn Material>,
+    bbox: Aabb,
 }
 
 impl Sphere {
-    pub fn new(center: Point3, radius: f64, mat: Rc<dyn Material>) -> Self {
+    pub fn new(center: Point3, radius: f64, mat: Arc<dyn Material>) -> Self {
+        let radius = radius.max(0.0);
+        let rvec = Vec3::new(radius, radius, radius);
+        let bbox = Aabb::from_points(&(center.clone() + -rvec.clone()), &(center.clone() + rvec));
         Sphere {
-            center,
-            radius: radius.max(0.0),
+            center: Ray::new(center, Vec3::default()),
+            radius,
             mat,
+            bbox,
+        }
+    }
+
+    /// Create a sphere moving at constant speed from `center1` at time 0 to `center2` at time 1
+    ///
+    /// The bounding box covers the whole path over that interval
+    pub fn moving(center1: Point3, center2: Point3, radius: f64, mat: Arc<dyn Material>) -> Self {
+        let radius = radius.max(0.0);
+        let rvec = Vec3::new(radius, radius, radius);
+        let box1 = Aabb::from_points(
+            &(center1.clone() + -rvec.clone()),
+            &(center1.clone() + rvec.clone()),
+        );
+        let box2 = Aabb::from_points(
+            &(center2.clone() + -rvec.clone()),
+            &(center2.clone() + rvec),
+        );
+        Sphere {
+            center: Ray::new(center1.clone(), center2 - center1),
+            radius,
+            mat,
+            bbox: Aabb::surrounding(&box1, &box2),
         }
     }
 }
 
-impl Hittable for Sphere {
+impl Sphere {
+    // Get the surface coordinates of the point `p` on the unit sphere centered at the origin
+    //
+    // u: angle around the Y axis from X=-1, mapped to [0, 1]
+    // v: angle from Y=-1 to Y=+1, mapped to [0, 1]
+    fn get_sphere_uv(p: &Vec3) -> (f64, f64) {
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    // Generate a direction in the cone, around +z, of the directions hitting a sphere of
+    // `radius` at squared distance `distance_squared` along +z
+    fn random_to_sphere(radius: f64, distance_squared: f64, sampler: &mut dyn Sampler) -> Vec3 {
+        let (r1, r2) = sampler.get_2d();
+        let z = 1.0 + r2 * ((1.0 - radius * radius / distance_squared).sqrt() - 1.0);
+
+        let phi = 2.0 * PI * r1;
+        let x = phi.cos() * (1.0 - z * z).sqrt();
+        let y = phi.sin() * (1.0 - z * z).sqrt();
+
+        Vec3::new(x, y, z)
+    }
+
     /// Store the information regarding the intersection of sphere in a `HitRecord` if the ray
     /// hit the Sphere within the `Interval`, and return true, else return false
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
-        let oc = &self.center - r.origin();
+    fn intersect(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let current_center = self.center.at(r.time());
+        let oc = &current_center - r.origin();
         let a = r.direction().length_squared();
         let h = r.direction().dot(&oc);
         let c = oc.length_squared() - self.radius * self.radius;
@@ -44,9 +99,124 @@ impl Hittable for Sphere {
 
         rec.t = root;
         rec.p = r.at(rec.t);
-        rec.set_face_normal(r, (&rec.p - &self.center) / self.radius);
-        rec.mat = Rc::clone(&self.mat);
+        let outward_normal = (&rec.p - &current_center) / self.radius;
+        (rec.u, rec.v) = Sphere::get_sphere_uv(&outward_normal);
+        rec.set_face_normal(r, outward_normal);
+        rec.mat = Arc::clone(&self.mat);
 
         true
     }
 }
+
+impl Hittable for Sphere {
+    fn hit(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> bool {
+        self.intersect(r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox.clone()
+    }
+
+    /// Uniform density over the cone of directions from `origin` hitting the sphere
+    ///
+    /// Moving spheres are sampled at their position at time 0
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let mut rec = HitRecord::default();
+        let r = Ray::new(origin.clone(), direction.clone());
+        if !self.intersect(&r, Interval::new(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = (self.center.origin() - origin).length_squared();
+        if distance_squared <= self.radius * self.radius {
+            // From inside, every direction hits the sphere
+            return 1.0 / (4.0 * PI);
+        }
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+
+        1.0 / solid_angle
+    }
+
+    /// Direction from `origin` towards a uniformly chosen point of the cone hitting the sphere,
+    /// at its position at time 0
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let direction = self.center.origin() - origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return Vec3::random_unit_vector(sampler);
+        }
+        let uvw = Onb::new(&direction);
+        uvw.transform(&Sphere::random_to_sphere(
+            self.radius,
+            distance_squared,
+            sampler,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod sphere_tests {
+    use super::*;
+    use crate::{IndependentSampler, Lambertian};
+
+    #[test]
+    fn test_sphere_uv_at_the_poles() {
+        let uv_close = |p: Vec3, u: f64, v: f64| {
+            let (pu, pv) = Sphere::get_sphere_uv(&p);
+            (pu - u).abs() < 1e-12 && (pv - v).abs() < 1e-12
+        };
+
+        assert!(uv_close(Vec3::new(1.0, 0.0, 0.0), 0.5, 0.5));
+        assert!(uv_close(Vec3::new(0.0, 0.0, 1.0), 0.25, 0.5));
+        assert!(uv_close(Vec3::new(0.0, 0.0, -1.0), 0.75, 0.5));
+        // u starts and ends at -x, so the seam can give either end
+        assert!(
+            uv_close(Vec3::new(-1.0, 0.0, 0.0), 0.0, 0.5)
+                || uv_close(Vec3::new(-1.0, 0.0, 0.0), 1.0, 0.5)
+        );
+        // u is arbitrary at the poles on the y axis
+        assert!((Sphere::get_sphere_uv(&Vec3::new(0.0, 1.0, 0.0)).1 - 1.0).abs() < 1e-12);
+        assert!(Sphere::get_sphere_uv(&Vec3::new(0.0, -1.0, 0.0)).1.abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_moving_sphere() {
+        let mut sampler = IndependentSampler::new(0);
+        let sphere = Sphere::moving(
+            Point3::new(0.0, 0.0, -2.0),
+            Point3::new(4.0, 0.0, -2.0),
+            0.5,
+            Arc::new(Lambertian::default()),
+        );
+
+        // A ray along -z through x hits the sphere only around the time its center is at x
+        let hit_at = |x: f64, time: f64, sampler: &mut IndependentSampler| {
+            let r = Ray::with_time(Point3::new(x, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), time);
+            let mut rec = HitRecord::default();
+            sphere
+                .hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec, sampler)
+                .then_some(rec)
+        };
+        for (x, time) in [(0.0, 0.0), (1.0, 0.25), (4.0, 1.0)] {
+            let rec = hit_at(x, time, &mut sampler).unwrap();
+            assert!((rec.t - 1.5).abs() < 1e-12);
+            assert!((&rec.p - &Point3::new(x, 0.0, -1.5)).length() < 1e-12);
+            assert!((&rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-12);
+        }
+        assert!(hit_at(4.0, 0.0, &mut sampler).is_none());
+        assert!(hit_at(0.0, 1.0, &mut sampler).is_none());
+
+        // The bounding box covers the sphere at both ends of its motion
+        let bbox = sphere.bounding_box();
+        assert_eq!((bbox.x.min, bbox.x.max), (-0.5, 4.5));
+        assert_eq!((bbox.y.min, bbox.y.max), (-0.5, 0.5));
+        assert_eq!((bbox.z.min, bbox.z.max), (-2.5, -1.5));
+    }
+}
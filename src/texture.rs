@@ -0,0 +1,399 @@
+use crate::{Color, Image, ImageError, Perlin, Point3};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Color which varies over a surface, looked up using the surface coordinates (u, v) and the
+/// point `p` of the hit
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+}
+
+/// Texture with the same color everywhere
+#[derive(Default)]
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Color) -> Self {
+        SolidColor { albedo }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.albedo.clone()
+    }
+}
+
+/// Checker pattern of cubes in space, alternating between the `even` and `odd` textures
+///
+/// The pattern depends only on the hit point, so it cuts through objects like a solid block
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    /// `scale` is the side of each cube
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        CheckerTexture {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+
+    pub fn from_colors(scale: f64, c1: Color, c2: Color) -> Self {
+        CheckerTexture::new(
+            scale,
+            Arc::new(SolidColor::new(c1)),
+            Arc::new(SolidColor::new(c2)),
+        )
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let x_integer = (self.inv_scale * p.x()).floor() as i64;
+        let y_integer = (self.inv_scale * p.y()).floor() as i64;
+        let z_integer = (self.inv_scale * p.z()).floor() as i64;
+
+        let is_even = (x_integer + y_integer + z_integer) % 2 == 0;
+        if is_even {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// Checker pattern over the surface coordinates, with `columns` squares along u and `rows`
+/// squares along v
+///
+/// Unlike `CheckerTexture` the pattern follows the surface, e.g. it wraps around a sphere
+pub struct UvCheckerTexture {
+    columns: f64,
+    rows: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl UvCheckerTexture {
+    pub fn new(columns: usize, rows: usize, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        UvCheckerTexture {
+            columns: columns as f64,
+            rows: rows as f64,
+            even,
+            odd,
+        }
+    }
+
+    pub fn from_colors(columns: usize, rows: usize, c1: Color, c2: Color) -> Self {
+        UvCheckerTexture::new(
+            columns,
+            rows,
+            Arc::new(SolidColor::new(c1)),
+            Arc::new(SolidColor::new(c2)),
+        )
+    }
+}
+
+impl Texture for UvCheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let u_integer = (u * self.columns).floor() as i64;
+        let v_integer = (v * self.rows).floor() as i64;
+
+        let is_even = (u_integer + v_integer) % 2 == 0;
+        if is_even {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// How `ImageTexture` picks the color between texel centers
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TextureFilter {
+    /// Color of the closest texel
+    Nearest,
+    /// Weighted average of the 4 closest texels
+    #[default]
+    Bilinear,
+}
+
+/// How `ImageTexture` handles surface coordinates outside of [0, 1]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WrapMode {
+    /// Tile the image
+    #[default]
+    Repeat,
+    /// Extend the texels on the edges
+    Clamp,
+    /// Tile the image, flipping every other copy
+    Mirror,
+}
+
+/// Texture mapping a bitmap onto the surface coordinates, with (0, 0) at the bottom left of
+/// the image
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    // Linear colors, converted from the sRGB values of the image
+    texels: Vec<Color>,
+    filter: TextureFilter,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    pub fn new(image: Image, filter: TextureFilter, wrap: WrapMode) -> Self {
+        let texels = image
+            .pixels()
+            .iter()
+            .map(|c| {
+                Color::new(
+                    srgb_to_linear(c.r()),
+                    srgb_to_linear(c.g()),
+                    srgb_to_linear(c.b()),
+                )
+            })
+            .collect();
+        ImageTexture {
+            width: image.width(),
+            height: image.height(),
+            texels,
+            filter,
+            wrap,
+        }
+    }
+
+    /// Read a PNG, PPM or PGM image from the file at `path`, filtered bilinearly and repeated
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ImageError> {
+        Ok(ImageTexture::new(
+            Image::load(path)?,
+            TextureFilter::default(),
+            WrapMode::default(),
+        ))
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let x = wrap_index(x, self.width, self.wrap);
+        let y = wrap_index(y, self.height, self.wrap);
+        self.texels[y * self.width + x].clone()
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        // Debugging aid: a texture without data is cyan
+        if self.texels.is_empty() {
+            return Color::new(0.0, 1.0, 1.0);
+        }
+
+        // Continuous texel coordinates, with the texel centers on integers. The image is
+        // stored from the top row, so v is flipped
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+
+        match self.filter {
+            TextureFilter::Nearest => self.texel(x.round() as i64, y.round() as i64),
+            TextureFilter::Bilinear => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let top = (1.0 - tx) * self.texel(x0, y0) + tx * self.texel(x0 + 1, y0);
+                let bottom = (1.0 - tx) * self.texel(x0, y0 + 1) + tx * self.texel(x0 + 1, y0 + 1);
+                (1.0 - ty) * top + ty * bottom
+            }
+        }
+    }
+}
+
+// Map any texel index into [0, size)
+fn wrap_index(index: i64, size: usize, wrap: WrapMode) -> usize {
+    let size = size as i64;
+    let index = match wrap {
+        WrapMode::Repeat => index.rem_euclid(size),
+        WrapMode::Clamp => index.clamp(0, size - 1),
+        WrapMode::Mirror => {
+            let index = index.rem_euclid(2 * size);
+            if index < size {
+                index
+            } else {
+                2 * size - 1 - index
+            }
+        }
+    };
+    index as usize
+}
+
+fn srgb_to_linear(component: f64) -> f64 {
+    if component <= 0.04045 {
+        component / 12.92
+    } else {
+        ((component + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Marble veins made by bending a sine wave along z with turbulence
+pub struct MarbleTexture {
+    noise: Perlin,
+    scale: f64,
+}
+
+impl MarbleTexture {
+    /// `scale` is the frequency of the veins, and `seed` picks the noise
+    pub fn new(scale: f64, seed: u64) -> Self {
+        MarbleTexture {
+            noise: Perlin::new(seed),
+            scale,
+        }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let phase = self.scale * p.z() + 10.0 * self.noise.turbulence(p, 7);
+        (0.5 * (1.0 + phase.sin())) * Color::new(1.0, 1.0, 1.0)
+    }
+}
+
+/// Growth rings around the y axis, wobbled by noise, alternating between a `light` and a `dark`
+/// color
+pub struct WoodTexture {
+    noise: Perlin,
+    scale: f64,
+    light: Color,
+    dark: Color,
+}
+
+impl WoodTexture {
+    /// `scale` is the number of rings per unit of distance from the y axis
+    pub fn new(scale: f64, light: Color, dark: Color, seed: u64) -> Self {
+        WoodTexture {
+            noise: Perlin::new(seed),
+            scale,
+            light,
+            dark,
+        }
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let radius = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        let rings = self.scale * radius + 2.0 * self.noise.fbm(p, 4, 2.0, 0.5);
+        // Sharpen the transition from the light early wood to the dark late wood of each ring
+        let t = (rings - rings.floor()).powi(3);
+        (1.0 - t) * self.light.clone() + t * self.dark.clone()
+    }
+}
+
+/// Clouds made of fractal Brownian motion, blending from the `sky` color to the `cloud` color
+pub struct CloudTexture {
+    noise: Perlin,
+    scale: f64,
+    sky: Color,
+    cloud: Color,
+}
+
+impl CloudTexture {
+    /// `scale` is the frequency of the largest cloud features
+    pub fn new(scale: f64, sky: Color, cloud: Color, seed: u64) -> Self {
+        CloudTexture {
+            noise: Perlin::new(seed),
+            scale,
+            sky,
+            cloud,
+        }
+    }
+}
+
+impl Texture for CloudTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let scaled = Point3::new(self.scale * p.x(), self.scale * p.y(), self.scale * p.z());
+        // Only the upper part of the noise makes clouds, leaving patches of clear sky
+        let density = (1.5 * self.noise.fbm(&scaled, 6, 2.0, 0.5) + 0.2).clamp(0.0, 1.0);
+        (1.0 - density) * self.sky.clone() + density * self.cloud.clone()
+    }
+}
+
+#[cfg(test)]
+mod texture_tests {
+    use super::*;
+
+    #[test]
+    fn test_image_texture_addressing() {
+        // One black and one white texel; white is 1.0 both before and after linearisation
+        let image = Image::new(
+            2,
+            1,
+            vec![Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0)],
+        );
+        let texture = ImageTexture::new(image, TextureFilter::Bilinear, WrapMode::Clamp);
+        let p = Point3::default();
+
+        assert_eq!(texture.value(0.25, 0.5, &p).r(), 0.0);
+        assert_eq!(texture.value(0.5, 0.5, &p).r(), 0.5);
+        assert_eq!(texture.value(2.0, 0.5, &p).r(), 1.0);
+
+        assert_eq!(wrap_index(-1, 4, WrapMode::Repeat), 3);
+        assert_eq!(wrap_index(-1, 4, WrapMode::Clamp), 0);
+        assert_eq!(wrap_index(-1, 4, WrapMode::Mirror), 0);
+        assert_eq!(wrap_index(5, 4, WrapMode::Mirror), 2);
+    }
+
+    #[test]
+    fn test_solid_color() {
+        let texture = SolidColor::new(Color::new(0.1, 0.2, 0.3));
+        let c = texture.value(0.7, 0.4, &Point3::new(5.0, -2.0, 1.0));
+        assert_eq!((c.r(), c.g(), c.b()), (0.1, 0.2, 0.3));
+    }
+
+    #[test]
+    fn test_checker_parity() {
+        let texture =
+            CheckerTexture::from_colors(1.0, Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0));
+        let is_even = |x, y, z| texture.value(0.0, 0.0, &Point3::new(x, y, z)).r() == 1.0;
+
+        // The color flips when crossing a cell boundary along any axis, including the ones
+        // through the origin
+        assert!(is_even(0.5, 0.5, 0.5));
+        assert!(!is_even(1.001, 0.5, 0.5));
+        assert!(!is_even(0.5, 1.001, 0.5));
+        assert!(!is_even(0.5, 0.5, 1.001));
+        assert!(!is_even(-0.5, 0.5, 0.5));
+        assert!(is_even(-0.5, -0.5, 0.5));
+        assert!(is_even(1.5, 1.5, 0.5));
+        assert!(is_even(0.999, 0.5, 0.5));
+
+        // The scale is the size of a cell
+        let texture =
+            CheckerTexture::from_colors(0.5, Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0));
+        let p = Point3::new(0.75, 0.25, 0.25);
+        assert_eq!(texture.value(0.0, 0.0, &p).r(), 0.0);
+    }
+
+    #[test]
+    fn test_uv_checker_parity() {
+        let texture = UvCheckerTexture::from_colors(
+            4,
+            2,
+            Color::new(1.0, 1.0, 1.0),
+            Color::new(0.0, 0.0, 0.0),
+        );
+        let p = Point3::default();
+        let is_even = |u, v| texture.value(u, v, &p).r() == 1.0;
+
+        // 4 columns of width 0.25 and 2 rows of height 0.5
+        assert!(is_even(0.1, 0.1));
+        assert!(is_even(0.249, 0.499));
+        assert!(!is_even(0.251, 0.1));
+        assert!(!is_even(0.1, 0.501));
+        assert!(is_even(0.3, 0.6));
+        assert!(is_even(0.99, 0.99));
+    }
+}
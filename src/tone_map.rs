@@ -0,0 +1,134 @@
+use crate::Color;
+
+/// Curve compressing linear radiance to the displayable range [0, 1]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ToneMapOperator {
+    /// Cut every component above 1, which blows out bright areas
+    #[default]
+    Clamp,
+    /// `x / (1 + x)`, which never quite reaches white
+    Reinhard,
+    /// Reinhard curve reaching white at `white_point`
+    ExtendedReinhard { white_point: f64 },
+    /// John Hable's filmic curve from Uncharted 2, with its exposure bias of 2 and white point
+    /// of 11.2, so radiance reaches white at 5.6
+    Hable,
+    /// Stephen Hill's fit of the ACES reference and sRGB output transforms, which also
+    /// desaturates very bright colors
+    AcesFitted,
+}
+
+/// Display transform applied to the linear radiance of a film before it is gamma encoded
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ToneMapping {
+    /// Exposure adjustment in stops: every stop doubles the radiance
+    pub exposure: f64,
+    pub operator: ToneMapOperator,
+}
+
+impl ToneMapping {
+    pub fn new(operator: ToneMapOperator, exposure: f64) -> Self {
+        ToneMapping { exposure, operator }
+    }
+
+    /// Map a linear radiance to a linear color with components in [0, 1]
+    pub fn apply(&self, color: &Color) -> Color {
+        let scale = self.exposure.exp2();
+        let [r, g, b] = [color.r(), color.g(), color.b()].map(|value| (scale * value).max(0.0));
+
+        let [r, g, b] = match self.operator {
+            ToneMapOperator::Clamp => [r, g, b],
+            ToneMapOperator::Reinhard => [r, g, b].map(|x| x / (1.0 + x)),
+            ToneMapOperator::ExtendedReinhard { white_point } => {
+                let inv_white_squared = 1.0 / (white_point * white_point);
+                [r, g, b].map(|x| x * (1.0 + x * inv_white_squared) / (1.0 + x))
+            }
+            ToneMapOperator::Hable => {
+                // The curve was designed for an exposure bias of 2
+                let inv_white = 1.0 / hable_partial(HABLE_WHITE_POINT);
+                [r, g, b].map(|x| hable_partial(2.0 * x) * inv_white)
+            }
+            ToneMapOperator::AcesFitted => aces_fitted([r, g, b]),
+        };
+
+        Color::new(r.min(1.0), g.min(1.0), b.min(1.0))
+    }
+}
+
+const HABLE_WHITE_POINT: f64 = 11.2;
+
+fn hable_partial(x: f64) -> f64 {
+    // Shoulder strength, linear strength, linear angle, toe strength, toe numerator and toe
+    // denominator
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+}
+
+// sRGB to the ACES working space, combined with the saturation adjustment of the reference
+// transform
+const ACES_INPUT: [[f64; 3]; 3] = [
+    [0.59719, 0.35458, 0.04823],
+    [0.07600, 0.90834, 0.01566],
+    [0.02840, 0.13383, 0.83777],
+];
+
+// Back to sRGB from the output transform space
+const ACES_OUTPUT: [[f64; 3]; 3] = [
+    [1.60475, -0.53108, -0.07367],
+    [-0.10208, 1.10813, -0.00605],
+    [-0.00327, -0.07276, 1.07602],
+];
+
+fn aces_fitted(rgb: [f64; 3]) -> [f64; 3] {
+    // Rational fit of the reference and output transforms
+    let curve = |v: f64| {
+        let numerator = v * (v + 0.0245786) - 0.000090537;
+        numerator / (v * (0.983729 * v + 0.432951) + 0.238081)
+    };
+    let mapped = multiply(&ACES_INPUT, rgb).map(curve);
+    multiply(&ACES_OUTPUT, mapped).map(|v| v.clamp(0.0, 1.0))
+}
+
+fn multiply(m: &[[f64; 3]; 3], v: [f64; 3]) -> [f64; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+#[cfg(test)]
+mod tone_map_tests {
+    use super::*;
+
+    #[test]
+    fn test_operators() {
+        let operators = [
+            ToneMapOperator::Clamp,
+            ToneMapOperator::Reinhard,
+            ToneMapOperator::ExtendedReinhard { white_point: 4.0 },
+            ToneMapOperator::Hable,
+            ToneMapOperator::AcesFitted,
+        ];
+        for operator in operators {
+            let mapping = ToneMapping::new(operator, 0.0);
+            let mut previous = -1.0;
+            for i in 0..200 {
+                let x = 0.05 * i as f64;
+                let value = mapping.apply(&Color::new(x, x, x)).g();
+                assert!((0.0..=1.0).contains(&value), "{operator:?}");
+                assert!(value >= previous, "{operator:?} decreases at {x}");
+                previous = value;
+            }
+            assert!(mapping.apply(&Color::default()).r().abs() < 1e-3);
+            assert!(mapping.apply(&Color::new(1e6, 1e6, 1e6)).b() > 0.99);
+        }
+
+        let white = |operator, x: f64| ToneMapping::new(operator, 0.0).apply(&Color::new(x, x, x));
+        assert!((white(ToneMapOperator::Reinhard, 1.0).r() - 0.5).abs() < 1e-12);
+        let extended = ToneMapOperator::ExtendedReinhard { white_point: 4.0 };
+        assert!((white(extended, 4.0).r() - 1.0).abs() < 1e-12);
+        assert!((white(ToneMapOperator::Hable, HABLE_WHITE_POINT / 2.0).r() - 1.0).abs() < 1e-12);
+
+        // Every stop doubles the radiance
+        let brighter = ToneMapping::new(ToneMapOperator::Clamp, 2.0);
+        assert!((brighter.apply(&Color::new(0.1, 0.2, -1.0)).g() - 0.8).abs() < 1e-12);
+        assert_eq!(brighter.apply(&Color::new(0.1, 0.2, -1.0)).b(), 0.0);
+    }
+}
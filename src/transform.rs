@@ -0,0 +1,232 @@
+use std::sync::Arc;
+
+use crate::{Aabb, HitRecord, Hittable, Interval, Mat4, Point3, Ray, Sampler, Vec3};
+
+/// Instance of a hittable placed in the world by an affine transformation
+///
+/// The wrapped object is shared, so the same object can be placed at many poses without
+/// copying it
+pub struct Transform {
+    object: Arc<dyn Hittable>,
+    // Object space to world space
+    matrix: Mat4,
+    // World space to object space
+    inverse: Mat4,
+    // Transforms the normals from object space to world space
+    normal_matrix: Mat4,
+    // Determinant of the linear part of `inverse`, which scales the solid angles
+    inverse_determinant: f64,
+    bbox: Aabb,
+}
+
+impl Transform {
+    /// Place `object` using the object to world space transformation `matrix`
+    ///
+    /// Returns `None` if the matrix isn't invertible, e.g. when it scales an axis to 0
+    pub fn new(object: Arc<dyn Hittable>, matrix: Mat4) -> Option<Self> {
+        let inverse = matrix.inverse()?;
+        let normal_matrix = inverse.transpose();
+        let inverse_determinant = linear_determinant(&inverse);
+        let bbox = transform_bounding_box(&object.bounding_box(), &matrix);
+        Some(Transform {
+            object,
+            matrix,
+            inverse,
+            normal_matrix,
+            inverse_determinant,
+            bbox,
+        })
+    }
+
+    pub fn translate(object: Arc<dyn Hittable>, offset: &Vec3) -> Option<Self> {
+        Transform::new(object, Mat4::translation(offset))
+    }
+
+    /// Rotate by `degrees` around `axis`, which goes through the origin
+    pub fn rotate(object: Arc<dyn Hittable>, axis: &Vec3, degrees: f64) -> Option<Self> {
+        Transform::new(object, Mat4::rotation(axis, degrees))
+    }
+
+    /// Scale by a different factor along every axis, relative to the origin
+    pub fn scale(object: Arc<dyn Hittable>, factors: &Vec3) -> Option<Self> {
+        Transform::new(object, Mat4::scaling(factors))
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        // The direction isn't normalized, so the distances along the ray are the same in
+        // both spaces
+        let object_ray = Ray::with_time(
+            self.inverse.transform_point(r.origin()),
+            self.inverse.transform_vector(r.direction()),
+            r.time(),
+        );
+        if !self.object.hit(&object_ray, ray_t, rec, sampler) {
+            return false;
+        }
+
+        // Transforming the normal by the inverse transpose keeps its dot product with the ray
+        // direction, so `front_face` stays valid
+        rec.p = self.matrix.transform_point(&rec.p);
+        rec.normal = self
+            .normal_matrix
+            .transform_vector(&rec.normal)
+            .unit_vector();
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox.clone()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let object_direction = self.inverse.transform_vector(direction);
+        let object_pdf = self
+            .object
+            .pdf_value(&self.inverse.transform_point(origin), &object_direction);
+        if object_pdf == 0.0 {
+            return 0.0;
+        }
+
+        // The linear map A from world to object directions changes the solid angle around the
+        // unit direction w by |det A| / |A w|^3, which is 1 for rigid transforms
+        let ratio = direction.length() / object_direction.length();
+        object_pdf * self.inverse_determinant.abs() * ratio * ratio * ratio
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let object_direction = self
+            .object
+            .random(&self.inverse.transform_point(origin), sampler);
+        self.matrix.transform_vector(&object_direction)
+    }
+}
+
+// Determinant of the upper left 3x3 block of `m`
+fn linear_determinant(m: &Mat4) -> f64 {
+    m.get(0, 0) * (m.get(1, 1) * m.get(2, 2) - m.get(1, 2) * m.get(2, 1))
+        - m.get(0, 1) * (m.get(1, 0) * m.get(2, 2) - m.get(1, 2) * m.get(2, 0))
+        + m.get(0, 2) * (m.get(1, 0) * m.get(2, 1) - m.get(1, 1) * m.get(2, 0))
+}
+
+// Bounding box in world space of the 8 corners of `bbox` once transformed
+fn transform_bounding_box(bbox: &Aabb, matrix: &Mat4) -> Aabb {
+    let mut min = [f64::INFINITY; 3];
+    let mut max = [f64::NEG_INFINITY; 3];
+    for corner in 0..8 {
+        let coordinate = |axis: usize| {
+            let interval = bbox.axis_interval(axis);
+            if (corner >> axis) & 1 == 0 {
+                interval.min
+            } else {
+                interval.max
+            }
+        };
+        let p = matrix.transform_point(&Point3::new(coordinate(0), coordinate(1), coordinate(2)));
+        for axis in 0..3 {
+            min[axis] = min[axis].min(p[axis]);
+            max[axis] = max[axis].max(p[axis]);
+        }
+    }
+    Aabb::from_points(
+        &Point3::new(min[0], min[1], min[2]),
+        &Point3::new(max[0], max[1], max[2]),
+    )
+}
+
+#[cfg(test)]
+mod transform_tests {
+    use super::*;
+    use crate::{IndependentSampler, Lambertian, Quad, Sphere};
+
+    #[test]
+    fn test_transformed_sphere_matches_placed_sphere() {
+        let mut sampler = IndependentSampler::new(0);
+        let mat = Arc::new(Lambertian::default());
+        let unit_sphere: Arc<dyn Hittable> =
+            Arc::new(Sphere::new(Point3::new(1.0, 0.0, 0.0), 1.0, mat.clone()));
+        let rotated =
+            Arc::new(Transform::rotate(unit_sphere, &Vec3::new(0.0, 0.0, 1.0), 90.0).unwrap());
+        let instance = Transform::translate(rotated, &Vec3::new(0.0, 0.0, -3.0)).unwrap();
+        let placed = Sphere::new(Point3::new(0.0, 1.0, -3.0), 1.0, mat);
+
+        let bbox = instance.bounding_box();
+        assert!((bbox.y.min - 0.0).abs() < 1e-9 && (bbox.y.max - 2.0).abs() < 1e-9);
+        assert!((bbox.z.min + 4.0).abs() < 1e-9 && (bbox.z.max + 2.0).abs() < 1e-9);
+
+        for i in 0..50 {
+            let direction = Vec3::new(0.01 * i as f64 - 0.25, 0.02 * i as f64 - 0.1, -1.0);
+            let r = Ray::new(Point3::new(0.0, 0.0, 0.0), direction);
+            let (mut expected, mut actual) = (HitRecord::default(), HitRecord::default());
+            let interval = Interval::new(0.001, f64::INFINITY);
+            let hit = placed.hit(&r, interval.clone(), &mut expected, &mut sampler);
+            assert_eq!(instance.hit(&r, interval, &mut actual, &mut sampler), hit);
+            if hit {
+                assert!((actual.t - expected.t).abs() < 1e-9);
+                assert!((&actual.p - &expected.p).length() < 1e-9);
+                assert!((&actual.normal - expected.normal.clone()).length() < 1e-9);
+                assert_eq!(actual.front_face, expected.front_face);
+            }
+        }
+    }
+
+    #[test]
+    fn test_light_sampling_matches_placed_quad() {
+        let mat = Arc::new(Lambertian::default());
+        let (q, u, v) = (
+            Point3::new(-0.5, -0.5, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let quad = Arc::new(Quad::new(q.clone(), u.clone(), v.clone(), mat.clone()));
+        // Not rigid, so the solid angles change too
+        let matrix = Mat4::translation(&Vec3::new(0.3, 2.0, -1.0))
+            * Mat4::rotation(&Vec3::new(1.0, 0.0, 0.0), 70.0)
+            * Mat4::scaling(&Vec3::new(2.0, 0.5, 1.5));
+        let placed = Quad::new(
+            matrix.transform_point(&q),
+            matrix.transform_vector(&u),
+            matrix.transform_vector(&v),
+            mat,
+        );
+        let instance = Transform::new(quad, matrix).unwrap();
+
+        let origin = Point3::new(0.1, -0.2, 0.4);
+        let mut sampler = IndependentSampler::new(7);
+        for _ in 0..100 {
+            let direction = instance.random(&origin, &mut sampler);
+            // Directions reach the placed quad, with the same density
+            let mut rec = HitRecord::default();
+            let r = Ray::new(origin.clone(), direction.clone());
+            assert!(placed.hit(
+                &r,
+                Interval::new(0.001, f64::INFINITY),
+                &mut rec,
+                &mut sampler
+            ));
+            assert!((rec.t - 1.0).abs() < 1e-9);
+            let expected = placed.pdf_value(&origin, &direction);
+            assert!((instance.pdf_value(&origin, &(2.0 * direction)) - expected).abs() < 1e-9);
+        }
+        assert_eq!(instance.pdf_value(&origin, &Vec3::new(0.0, -1.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn test_singular_matrix_is_rejected() {
+        let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::default()),
+        ));
+        assert!(Transform::scale(sphere.clone(), &Vec3::new(1.0, 0.0, 1.0)).is_none());
+        assert!(Transform::scale(sphere, &Vec3::new(1.0, 2.0, 1.0)).is_some());
+    }
+}
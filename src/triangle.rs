@@ -0,0 +1,618 @@
+use crate::{Aabb, HitRecord, Hittable, Interval, Material, Point3, Ray, SahBvh, Sampler, Vec3};
+use std::sync::Arc;
+
+/// Triangle given by its 3 vertices
+///
+/// The vertices can optionally carry normals, which are interpolated over the triangle for
+/// smooth shading, and texture coordinates
+pub struct Triangle {
+    vertices: [Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    mat: Arc<dyn Material>,
+    bbox: Aabb,
+}
+
+impl Triangle {
+    pub fn new(a: Point3, b: Point3, c: Point3, mat: Arc<dyn Material>) -> Self {
+        Triangle::with_attributes([a, b, c], None, None, mat)
+    }
+
+    /// Create a triangle with per-vertex `normals` and texture coordinates `uvs`
+    pub fn with_attributes(
+        vertices: [Point3; 3],
+        normals: Option<[Vec3; 3]>,
+        uvs: Option<[(f64, f64); 3]>,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        let bbox = triangle_bounding_box(&vertices[0], &vertices[1], &vertices[2]);
+        Triangle {
+            vertices,
+            normals,
+            uvs,
+            mat,
+            bbox,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> bool {
+        let [p0, p1, p2] = &self.vertices;
+        let Some((t, b1, b2)) = intersect_triangle(p0, p1, p2, r, &ray_t) else {
+            return false;
+        };
+
+        let normals = self
+            .normals
+            .as_ref()
+            .map(|[n0, n1, n2]| [n0.clone(), n1.clone(), n2.clone()]);
+        set_hit_record(
+            rec,
+            r,
+            t,
+            [p0, p1, p2],
+            normals,
+            self.uvs,
+            (b1, b2),
+            &self.mat,
+        );
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox.clone()
+    }
+
+    /// Uniform density over the area of the triangle, converted to solid angle from `origin`
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let [p0, p1, p2] = &self.vertices;
+        area_pdf_value([p0, p1, p2], origin, direction)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let [p0, p1, p2] = &self.vertices;
+        area_random([p0, p1, p2], origin, sampler)
+    }
+}
+
+/// Collection of triangles sharing the same vertex buffers
+///
+/// Every triangle only stores the indices of its vertices in the buffers. The triangles are
+/// kept in their own bounding volume hierarchy, so the mesh is a single hittable no matter
+/// how many triangles it has.
+pub struct TriangleMesh {
+    bvh: SahBvh,
+    // The triangles again, in their original order, to pick one when sampling the mesh
+    triangles: Vec<Arc<MeshTriangle>>,
+    // Total area of the triangles up to every one of them, included
+    cumulative_areas: Vec<f64>,
+}
+
+// Vertex buffers of a mesh
+struct MeshBuffers {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,   // Empty, or one normal per position
+    uvs: Vec<(f64, f64)>, // Empty, or one texture coordinate per position
+    mat: Arc<dyn Material>,
+}
+
+// Triangle of a mesh referring to its vertices by their index in the buffers
+struct MeshTriangle {
+    mesh: Arc<MeshBuffers>,
+    indices: [usize; 3],
+}
+
+impl TriangleMesh {
+    /// Create a mesh from the vertex buffers and 3 vertex indices for every triangle
+    ///
+    /// `normals` and `uvs` should either be empty or have an entry for every position.
+    /// Panics if an index is out of range of the positions.
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        indices: Vec<[usize; 3]>,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        assert!(
+            indices
+                .iter()
+                .flatten()
+                .all(|&index| index < positions.len()),
+            "Triangle vertex index out of range"
+        );
+        assert!(normals.is_empty() || normals.len() == positions.len());
+        assert!(uvs.is_empty() || uvs.len() == positions.len());
+
+        let mesh = Arc::new(MeshBuffers {
+            positions,
+            normals,
+            uvs,
+            mat,
+        });
+        let triangles: Vec<Arc<MeshTriangle>> = indices
+            .into_iter()
+            .map(|indices| {
+                Arc::new(MeshTriangle {
+                    mesh: mesh.clone(),
+                    indices,
+                })
+            })
+            .collect();
+        let cumulative_areas = triangles
+            .iter()
+            .scan(0.0, |total, triangle| {
+                *total += triangle.area();
+                Some(*total)
+            })
+            .collect();
+        let bvh = SahBvh::new(
+            triangles
+                .iter()
+                .map(|triangle| triangle.clone() as Arc<dyn Hittable>)
+                .collect(),
+        );
+
+        TriangleMesh {
+            bvh,
+            triangles,
+            cumulative_areas,
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    fn area(&self) -> f64 {
+        self.cumulative_areas.last().copied().unwrap_or(0.0)
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        self.bvh.hit(r, ray_t, rec, sampler)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+
+    /// Uniform density over the area of the whole mesh, converted to solid angle from `origin`
+    ///
+    /// Every triangle along the direction contributes, so the cost grows with the triangle
+    /// count; lights are best kept to small meshes
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let area = self.area();
+        if area <= 0.0 {
+            return 0.0;
+        }
+        // Each triangle is picked with a probability proportional to its area
+        self.triangles
+            .iter()
+            .map(|triangle| triangle.area() / area * triangle.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        if self.triangles.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let target = sampler.get_1d() * self.area();
+        let index = self
+            .cumulative_areas
+            .partition_point(|&total| total <= target)
+            .min(self.triangles.len() - 1);
+        self.triangles[index].random(origin, sampler)
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> bool {
+        let mesh = &self.mesh;
+        let [i0, i1, i2] = self.indices;
+        let (p0, p1, p2) = (
+            &mesh.positions[i0],
+            &mesh.positions[i1],
+            &mesh.positions[i2],
+        );
+        let Some((t, b1, b2)) = intersect_triangle(p0, p1, p2, r, &ray_t) else {
+            return false;
+        };
+
+        let normals = (!mesh.normals.is_empty()).then(|| {
+            [
+                mesh.normals[i0].clone(),
+                mesh.normals[i1].clone(),
+                mesh.normals[i2].clone(),
+            ]
+        });
+        let uvs = (!mesh.uvs.is_empty()).then(|| [mesh.uvs[i0], mesh.uvs[i1], mesh.uvs[i2]]);
+        set_hit_record(rec, r, t, [p0, p1, p2], normals, uvs, (b1, b2), &mesh.mat);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [p0, p1, p2] = self.vertices();
+        triangle_bounding_box(p0, p1, p2)
+    }
+
+    /// Uniform density over the area of the triangle, converted to solid angle from `origin`
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        area_pdf_value(self.vertices(), origin, direction)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        area_random(self.vertices(), origin, sampler)
+    }
+}
+
+impl MeshTriangle {
+    fn vertices(&self) -> [&Point3; 3] {
+        self.indices.map(|index| &self.mesh.positions[index])
+    }
+
+    fn area(&self) -> f64 {
+        let [p0, p1, p2] = self.vertices();
+        0.5 * (p1 - p0).cross(&(p2 - p0)).length()
+    }
+}
+
+fn triangle_bounding_box(p0: &Point3, p1: &Point3, p2: &Point3) -> Aabb {
+    Aabb::surrounding(&Aabb::from_points(p0, p1), &Aabb::from_points(p1, p2))
+}
+
+// Uniform density over the area of the triangle `p`, converted to solid angle from `origin`
+fn area_pdf_value([p0, p1, p2]: [&Point3; 3], origin: &Point3, direction: &Vec3) -> f64 {
+    let r = Ray::new(origin.clone(), direction.clone());
+    let Some((t, _, _)) = intersect_triangle(p0, p1, p2, &r, &Interval::new(0.001, f64::INFINITY))
+    else {
+        return 0.0;
+    };
+
+    let n = (p1 - p0).cross(&(p2 - p0));
+    let area = 0.5 * n.length();
+    let distance_squared = t * t * direction.length_squared();
+    // The geometric normal, as the shading normal may differ
+    let cosine = (direction.dot(&n) / (direction.length() * n.length())).abs();
+
+    distance_squared / (cosine * area)
+}
+
+// Direction from `origin` to a point picked uniformly over the area of the triangle `p`
+fn area_random([p0, p1, p2]: [&Point3; 3], origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+    // Fold the unit square onto the triangle, keeping the density uniform
+    let (r1, r2) = sampler.get_2d();
+    let s = r1.sqrt();
+    let (b1, b2) = (s * (1.0 - r2), s * r2);
+
+    let p = p0.clone() + (b1 * (p1 - p0)) + (b2 * (p2 - p0));
+    p - origin.clone()
+}
+
+// Möller–Trumbore intersection
+//
+// Solves o + t d = (1 - b1 - b2) p0 + b1 p1 + b2 p2 for t and the barycentric coordinates
+// (b1, b2) using Cramer's rule, and returns them if the ray hits the triangle within `ray_t`
+fn intersect_triangle(
+    p0: &Point3,
+    p1: &Point3,
+    p2: &Point3,
+    r: &Ray,
+    ray_t: &Interval,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+    let pvec = r.direction().cross(&edge2);
+    let det = edge1.dot(&pvec);
+
+    // No hit if the ray is parallel to the plane of the triangle. Nearly parallel rays give
+    // huge or invalid values, which the checks below reject, so there is no threshold which
+    // would depend on the scale of the triangle
+    if det == 0.0 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = r.origin() - p0;
+    let b1 = tvec.dot(&pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(&edge1);
+    let b2 = r.direction().dot(&qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(&qvec) * inv_det;
+    if !ray_t.surrounds(t) {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+// Fill the hit record of a ray hitting the triangle `p` at barycentric coordinates (b1, b2)
+#[allow(clippy::too_many_arguments)]
+fn set_hit_record(
+    rec: &mut HitRecord,
+    r: &Ray,
+    t: f64,
+    p: [&Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    (b1, b2): (f64, f64),
+    mat: &Arc<dyn Material>,
+) {
+    let b0 = 1.0 - b1 - b2;
+
+    rec.t = t;
+    rec.p = r.at(t);
+    rec.barycentric = [b0, b1, b2];
+    rec.mat = Arc::clone(mat);
+
+    // Triangles without texture coordinates are mapped as if their vertices were at
+    // (0, 0), (1, 0) and (0, 1)
+    (rec.u, rec.v) = match uvs {
+        Some([uv0, uv1, uv2]) => (
+            b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+            b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+        ),
+        None => (b1, b2),
+    };
+
+    // The geometric normal decides which side was hit
+    let geometric_normal = (p[1] - p[0]).cross(&(p[2] - p[0])).unit_vector();
+    rec.set_face_normal(r, geometric_normal);
+
+    // Interpolated vertex normals only change the shading, on the side which was hit
+    if let Some([n0, n1, n2]) = normals {
+        let shading_normal = (b0 * n0 + b1 * n1 + b2 * n2).unit_vector();
+        rec.normal = if rec.front_face {
+            shading_normal
+        } else {
+            -shading_normal
+        };
+    }
+}
+
+#[cfg(test)]
+mod triangle_tests {
+    use super::*;
+    use crate::{HittableList, IndependentSampler, Lambertian, Quad};
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+    fn unit_triangle() -> Triangle {
+        Triangle::with_attributes(
+            [
+                Point3::new(0.0, 0.0, -1.0),
+                Point3::new(1.0, 0.0, -1.0),
+                Point3::new(0.0, 1.0, -1.0),
+            ],
+            Some([
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(1.0, 0.0, 1.0),
+                Vec3::new(0.0, 1.0, 1.0),
+            ]),
+            Some([(0.5, 0.5), (1.0, 0.25), (0.0, 1.0)]),
+            Arc::new(Lambertian::default()),
+        )
+    }
+
+    fn hit(object: &dyn Hittable, origin: Point3, direction: Vec3) -> Option<HitRecord> {
+        let mut rec = HitRecord::default();
+        let r = Ray::new(origin, direction);
+        object
+            .hit(
+                &r,
+                Interval::new(0.001, f64::INFINITY),
+                &mut rec,
+                &mut IndependentSampler::new(0),
+            )
+            .then_some(rec)
+    }
+
+    #[test]
+    fn test_hit_and_miss() {
+        let triangle = unit_triangle();
+        let origin = Point3::new(0.0, 0.0, 0.0);
+
+        let rec = hit(&triangle, origin.clone(), Vec3::new(0.2, 0.3, -1.0)).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!((&rec.p - &Point3::new(0.2, 0.3, -1.0)).length() < 1e-12);
+        assert!(rec.front_face);
+
+        // Just outside the hypotenuse, and outside the other two edges
+        assert!(hit(&triangle, origin.clone(), Vec3::new(0.51, 0.5, -1.0)).is_none());
+        assert!(hit(&triangle, origin.clone(), Vec3::new(-0.01, 0.5, -1.0)).is_none());
+        assert!(hit(&triangle, origin.clone(), Vec3::new(0.5, -0.01, -1.0)).is_none());
+        // Behind the ray
+        assert!(hit(&triangle, origin, Vec3::new(0.2, 0.3, 1.0)).is_none());
+        // Parallel to the plane, in it and above it
+        for z in [-1.0, -0.5] {
+            let parallel = hit(
+                &triangle,
+                Point3::new(-1.0, 0.2, z),
+                Vec3::new(1.0, 0.0, 0.0),
+            );
+            assert!(parallel.is_none());
+        }
+    }
+
+    #[test]
+    fn test_barycentric_coordinates_and_normal() {
+        let triangle = unit_triangle();
+        let origin = Point3::new(0.0, 0.0, 0.0);
+
+        // Plain triangles map their vertices to (0, 0), (1, 0) and (0, 1)
+        let plain = Triangle::new(
+            Point3::new(0.0, 0.0, -1.0),
+            Point3::new(1.0, 0.0, -1.0),
+            Point3::new(0.0, 1.0, -1.0),
+            Arc::new(Lambertian::default()),
+        );
+        let vertices = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)];
+        let uvs = [(0.5, 0.5), (1.0, 0.25), (0.0, 1.0)];
+        for (i, &(x, y)) in vertices.iter().enumerate() {
+            let direction = Vec3::new(x, y, -1.0);
+            let rec = hit(&plain, origin.clone(), direction.clone()).unwrap();
+            assert!((rec.u - x).abs() < 1e-12 && (rec.v - y).abs() < 1e-12);
+            assert!((rec.barycentric[i] - 1.0).abs() < 1e-12);
+
+            let rec = hit(&triangle, origin.clone(), direction).unwrap();
+            assert!((rec.u - uvs[i].0).abs() < 1e-12 && (rec.v - uvs[i].1).abs() < 1e-12);
+        }
+
+        // The vertex normals are averaged at the centroid
+        let rec = hit(&triangle, origin, Vec3::new(1.0 / 3.0, 1.0 / 3.0, -1.0)).unwrap();
+        let expected = Vec3::new(1.0, 1.0, 3.0).unit_vector();
+        assert!((&rec.normal - expected).length() < 1e-12);
+        assert!(
+            rec.barycentric
+                .iter()
+                .all(|&b| (b - 1.0 / 3.0).abs() < 1e-12)
+        );
+    }
+
+    #[test]
+    fn test_small_triangle() {
+        let mut sampler = IndependentSampler::new(0);
+        // Determinants around 1e-21, far below any fixed threshold
+        let scale = 1e-7;
+        let triangle = Triangle::new(
+            Point3::new(0.0, 0.0, -scale),
+            Point3::new(scale, 0.0, -scale),
+            Point3::new(0.0, scale, -scale),
+            Arc::new(Lambertian::default()),
+        );
+        let r = Ray::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.2 * scale, 0.3 * scale, -scale),
+        );
+        let mut rec = HitRecord::default();
+        assert!(triangle.hit(
+            &r,
+            Interval::new(0.0, f64::INFINITY),
+            &mut rec,
+            &mut sampler
+        ));
+        assert!((rec.t - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_mesh_matches_linear_scan() {
+        let mut rng = SmallRng::seed_from_u64(7);
+        let point = |rng: &mut SmallRng| {
+            Point3::new(
+                rng.random_range(-2.0..2.0),
+                rng.random_range(-2.0..2.0),
+                rng.random_range(-2.0..2.0),
+            )
+        };
+        let mat: Arc<dyn Material> = Arc::new(Lambertian::default());
+
+        let positions: Vec<Point3> = (0..150).map(|_| point(&mut rng)).collect();
+        let indices: Vec<[usize; 3]> = (0..50).map(|i| [3 * i, 3 * i + 1, 3 * i + 2]).collect();
+        let mut list = HittableList::default();
+        for [a, b, c] in &indices {
+            list.add(Arc::new(Triangle::new(
+                positions[*a].clone(),
+                positions[*b].clone(),
+                positions[*c].clone(),
+                mat.clone(),
+            )));
+        }
+        let mesh = TriangleMesh::new(positions, Vec::new(), Vec::new(), indices, mat);
+        assert_eq!(mesh.triangle_count(), 50);
+
+        let mut hits = 0;
+        for _ in 0..500 {
+            let origin = Point3::new(
+                rng.random_range(-6.0..6.0),
+                rng.random_range(-6.0..6.0),
+                rng.random_range(-6.0..6.0),
+            );
+            let direction = &point(&mut rng) - &origin;
+            let expected = hit(&list, origin.clone(), direction.clone());
+            let actual = hit(&mesh, origin, direction);
+            assert_eq!(actual.is_some(), expected.is_some());
+            if let (Some(actual), Some(expected)) = (actual, expected) {
+                assert!((actual.t - expected.t).abs() < 1e-9);
+                assert!((&actual.normal - expected.normal).length() < 1e-9);
+                hits += 1;
+            }
+        }
+        assert!(hits > 100);
+    }
+
+    #[test]
+    fn test_empty_mesh() {
+        let mut sampler = IndependentSampler::new(0);
+        let mesh = TriangleMesh::new(
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Arc::new(Lambertian::default()),
+        );
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let direction = mesh.random(&origin, &mut sampler);
+        assert!((&direction - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-12);
+        assert_eq!(mesh.pdf_value(&origin, &direction), 0.0);
+        assert!(hit(&mesh, origin, direction).is_none());
+    }
+
+    #[test]
+    fn test_mesh_light_density() {
+        // A square made of two triangles has the density of the same quad
+        let mat: Arc<dyn Material> = Arc::new(Lambertian::default());
+        let mesh = TriangleMesh::new(
+            vec![
+                Point3::new(-1.0, 2.0, -1.0),
+                Point3::new(1.0, 2.0, -1.0),
+                Point3::new(1.0, 2.0, 1.0),
+                Point3::new(-1.0, 2.0, 1.0),
+            ],
+            Vec::new(),
+            Vec::new(),
+            vec![[0, 1, 2], [0, 2, 3]],
+            mat.clone(),
+        );
+        let quad = Quad::new(
+            Point3::new(-1.0, 2.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 2.0),
+            mat,
+        );
+
+        let origin = Point3::new(0.3, 0.0, -0.2);
+        let mut sampler = IndependentSampler::new(3);
+        for _ in 0..200 {
+            let direction = mesh.random(&origin, &mut sampler);
+            let pdf = mesh.pdf_value(&origin, &direction);
+            assert!(pdf > 0.0);
+            assert!((pdf - quad.pdf_value(&origin, &direction)).abs() < 1e-9 * pdf);
+        }
+        assert_eq!(mesh.pdf_value(&origin, &Vec3::new(0.0, -1.0, 0.0)), 0.0);
+    }
+}
@@ -1,6 +1,13 @@
-use rand::Rng;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
 use std::f64::consts::PI;
 
+thread_local! {
+    // Every thread owns a generator, so threads never wait on each other for random numbers
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_os_rng());
+}
+
 /// Convert degress to radians
 pub fn degrees_to_radians(degress: f64) -> f64 {
     degress * PI / 180.0
@@ -8,7 +15,6 @@ pub fn degrees_to_radians(degress: f64) -> f64 {
 
 /// Generate a random f64 number in the range [min, max)
 pub fn random_f64(min: f64, max: f64) -> f64 {
-    let mut rng = rand::rng(); // TODO: see if this is needed as it may slow us down
-    let random_float: f64 = rng.random();
+    let random_float: f64 = RNG.with(|rng| rng.borrow_mut().random());
     min + (max - min) * random_float
 }
@@ -1,5 +1,6 @@
-use crate::utils;
-use std::ops::{Add, Div, Mul, Neg, Sub};
+use crate::Sampler;
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+use std::ops::{Add, Div, Index, Mul, Neg, Sub};
 
 /// Represent a collection of 3 values
 /// It can represent a point in 3D space or RGB values of a color via the "New Type Pattern"
@@ -52,43 +53,49 @@ impl Vec3 {
     }
 
     /// Generate a random vector with value of all components between `min` and `max`
-    pub fn random(min: f64, max: f64) -> Self {
+    pub fn random(min: f64, max: f64, sampler: &mut dyn Sampler) -> Self {
         Self::new(
-            utils::random_f64(min, max),
-            utils::random_f64(min, max),
-            utils::random_f64(min, max),
+            sampler.random_f64(min, max),
+            sampler.random_f64(min, max),
+            sampler.random_f64(min, max),
         )
     }
 
     /// Generate a random point in unit disk at z=0
-    pub fn random_in_unit_disk() -> Vec3 {
-        loop {
-            let p = Vec3::new(
-                utils::random_f64(-1.0, 1.0),
-                utils::random_f64(-1.0, 1.0),
-                0.0,
-            );
-
-            if p.length_squared() < 1.0 {
-                return p;
-            }
+    ///
+    /// Uses the concentric mapping of the unit square onto the disk, so every point takes
+    /// exactly 2 dimensions of the sampler and stratified samples stay stratified
+    pub fn random_in_unit_disk(sampler: &mut dyn Sampler) -> Vec3 {
+        let (u, v) = sampler.get_2d();
+        let offset_x = 2.0 * u - 1.0;
+        let offset_y = 2.0 * v - 1.0;
+        if offset_x == 0.0 && offset_y == 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
         }
+
+        let (r, theta) = if offset_x.abs() > offset_y.abs() {
+            (offset_x, FRAC_PI_4 * (offset_y / offset_x))
+        } else {
+            (offset_y, FRAC_PI_2 - FRAC_PI_4 * (offset_x / offset_y))
+        };
+        Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
     }
 
     /// Generate a random unit vector on a sphere
-    pub fn random_unit_vector() -> Self {
-        loop {
-            let p = Vec3::random(-1.0, 1.0);
-            let len_squared = p.length_squared();
-            if len_squared > 1e-160 && len_squared <= 1.0 {
-                return p / len_squared.sqrt();
-            }
-        }
+    ///
+    /// Maps 2 dimensions of the sampler to the sphere, picking the height uniformly
+    /// (Archimedes' hat-box theorem) and then the angle around the z axis
+    pub fn random_unit_vector(sampler: &mut dyn Sampler) -> Self {
+        let (u, v) = sampler.get_2d();
+        let z = 1.0 - 2.0 * u;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
     }
 
     /// Generate a random vector on same hemisphere as the sphere normal
-    pub fn random_on_hemisphere(normal: &Vec3) -> Self {
-        let on_unit_sphere = Self::random_unit_vector();
+    pub fn random_on_hemisphere(normal: &Vec3, sampler: &mut dyn Sampler) -> Self {
+        let on_unit_sphere = Self::random_unit_vector(sampler);
 
         // In the same hemisphere as the normal
         if on_unit_sphere.dot(normal) > 0.0 {
@@ -98,6 +105,15 @@ impl Vec3 {
         }
     }
 
+    /// Generate a random direction around +z with a density proportional to the cosine of its
+    /// angle with +z
+    pub fn random_cosine_direction(sampler: &mut dyn Sampler) -> Self {
+        let (u, v) = sampler.get_2d();
+        let phi = 2.0 * PI * u;
+        let r = v.sqrt();
+        Vec3::new(r * phi.cos(), r * phi.sin(), (1.0 - v).sqrt())
+    }
+
     /// Returns true if the vector is near-zero in all dimensions
     pub fn near_zero(&self) -> bool {
         let epsilon = 1e-8;
@@ -118,6 +134,14 @@ impl Vec3 {
     }
 }
 
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &Self::Output {
+        &self.e[axis]
+    }
+}
+
 impl Neg for Vec3 {
     type Output = Self;
 
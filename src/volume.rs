@@ -0,0 +1,366 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::{
+    Aabb, Color, HitRecord, Hittable, Interval, Material, Perlin, Point3, Ray, Sampler,
+    ScatterRecord, SpherePdf, Vec3, constant_medium::inside_stretch,
+};
+
+/// Density of a participating medium varying in space, with the albedo and emission of its
+/// particles
+pub trait DensityField: Send + Sync {
+    /// Extinction coefficient at `p`
+    fn density(&self, p: &Point3) -> f64;
+
+    /// Upper bound of the density everywhere, used as the majorant when tracking rays
+    fn max_density(&self) -> f64;
+
+    /// Fraction of the light hitting a particle which is scattered instead of absorbed
+    fn albedo(&self, _p: &Point3) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    /// Radiance emitted by the absorbing particles, e.g. the glow of fire
+    fn emission(&self, _p: &Point3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+}
+
+/// Dense grid of voxels filling `bounds`, interpolated trilinearly between the voxel centers
+///
+/// The voxels are stored with x varying fastest, then y, then z
+pub struct VoxelGrid {
+    bounds: Aabb,
+    resolution: [usize; 3],
+    density: Vec<f64>,
+    albedo: Option<Vec<Color>>,
+    emission: Option<Vec<Color>>,
+    max_density: f64,
+}
+
+impl VoxelGrid {
+    /// Panics if the resolution is zero along an axis, or if `density` doesn't have exactly one
+    /// value for every voxel
+    pub fn new(bounds: Aabb, resolution: [usize; 3], density: Vec<f64>) -> Self {
+        assert!(
+            resolution.iter().all(|&n| n > 0),
+            "VoxelGrid resolution must be positive"
+        );
+        assert_eq!(
+            density.len(),
+            resolution.iter().product::<usize>(),
+            "VoxelGrid density size mismatch"
+        );
+        let max_density = density.iter().cloned().fold(0.0, f64::max);
+        VoxelGrid {
+            bounds,
+            resolution,
+            density,
+            albedo: None,
+            emission: None,
+            max_density,
+        }
+    }
+
+    /// Set the albedo of every voxel; without it the albedo is white
+    pub fn with_albedo(mut self, albedo: Vec<Color>) -> Self {
+        assert_eq!(
+            albedo.len(),
+            self.density.len(),
+            "VoxelGrid albedo size mismatch"
+        );
+        self.albedo = Some(albedo);
+        self
+    }
+
+    /// Set the emission of every voxel; without it the volume doesn't glow
+    pub fn with_emission(mut self, emission: Vec<Color>) -> Self {
+        assert_eq!(
+            emission.len(),
+            self.density.len(),
+            "VoxelGrid emission size mismatch"
+        );
+        self.emission = Some(emission);
+        self
+    }
+
+    pub fn bounds(&self) -> &Aabb {
+        &self.bounds
+    }
+
+    // Indices and weights of the 8 voxels around `p`, or `None` outside the grid
+    fn corners(&self, p: &Point3) -> Option<[(usize, f64); 8]> {
+        let mut base = [0usize; 3];
+        let mut fraction = [0.0; 3];
+        for axis in 0..3 {
+            let interval = self.bounds.axis_interval(axis);
+            if !interval.contains(p[axis]) {
+                return None;
+            }
+            let n = self.resolution[axis];
+            // Continuous voxel coordinate, with the voxel centers on integers
+            let x = ((p[axis] - interval.min) / interval.size() * n as f64 - 0.5)
+                .clamp(0.0, (n - 1) as f64);
+            base[axis] = (x.floor() as usize).min(n.saturating_sub(2));
+            fraction[axis] = x - base[axis] as f64;
+        }
+
+        let [nx, ny, _] = self.resolution;
+        let mut corners = [(0, 0.0); 8];
+        for (corner, entry) in corners.iter_mut().enumerate() {
+            let mut index = [0usize; 3];
+            let mut weight = 1.0;
+            for axis in 0..3 {
+                let offset = (corner >> axis) & 1;
+                index[axis] = (base[axis] + offset).min(self.resolution[axis] - 1);
+                weight *= if offset == 1 {
+                    fraction[axis]
+                } else {
+                    1.0 - fraction[axis]
+                };
+            }
+            *entry = ((index[2] * ny + index[1]) * nx + index[0], weight);
+        }
+        Some(corners)
+    }
+
+    fn lookup_color(&self, values: &Option<Vec<Color>>, p: &Point3, default: Color) -> Color {
+        match (values, self.corners(p)) {
+            (Some(values), Some(corners)) => corners
+                .iter()
+                .fold(Color::new(0.0, 0.0, 0.0), |sum, &(index, weight)| {
+                    sum + weight * values[index].clone()
+                }),
+            _ => default,
+        }
+    }
+}
+
+impl DensityField for VoxelGrid {
+    fn density(&self, p: &Point3) -> f64 {
+        self.corners(p).map_or(0.0, |corners| {
+            corners
+                .iter()
+                .map(|&(index, weight)| weight * self.density[index])
+                .sum()
+        })
+    }
+
+    fn max_density(&self) -> f64 {
+        self.max_density
+    }
+
+    fn albedo(&self, p: &Point3) -> Color {
+        self.lookup_color(&self.albedo, p, Color::new(1.0, 1.0, 1.0))
+    }
+
+    fn emission(&self, p: &Point3) -> Color {
+        self.lookup_color(&self.emission, p, Color::new(0.0, 0.0, 0.0))
+    }
+}
+
+/// Billowing density made of fractal Brownian motion, like smoke or clouds
+pub struct NoiseDensity {
+    noise: Perlin,
+    scale: f64,
+    max_density: f64,
+    albedo: Color,
+}
+
+impl NoiseDensity {
+    /// `scale` is the frequency of the largest features, and the density varies between 0 and
+    /// `max_density`
+    pub fn new(scale: f64, max_density: f64, albedo: Color, seed: u64) -> Self {
+        NoiseDensity {
+            noise: Perlin::new(seed),
+            scale,
+            max_density,
+            albedo,
+        }
+    }
+}
+
+impl DensityField for NoiseDensity {
+    fn density(&self, p: &Point3) -> f64 {
+        let scaled = Point3::new(self.scale * p.x(), self.scale * p.y(), self.scale * p.z());
+        let value = 0.5 + self.noise.fbm(&scaled, 5, 2.0, 0.5);
+        self.max_density * value.clamp(0.0, 1.0)
+    }
+
+    fn max_density(&self) -> f64 {
+        self.max_density
+    }
+
+    fn albedo(&self, _p: &Point3) -> Color {
+        self.albedo.clone()
+    }
+}
+
+/// Participating medium whose density is given by a `DensityField`, filling the inside of a
+/// closed `boundary`
+///
+/// Rays are tracked through the medium against the maximum density (delta tracking), which
+/// finds the scattering points without bias however the density varies
+pub struct HeterogeneousMedium {
+    boundary: Arc<dyn Hittable>,
+    field: Arc<dyn DensityField>,
+    material: Arc<dyn Material>,
+}
+
+impl HeterogeneousMedium {
+    pub fn new(boundary: Arc<dyn Hittable>, field: Arc<dyn DensityField>) -> Self {
+        HeterogeneousMedium {
+            boundary,
+            field: Arc::clone(&field),
+            material: Arc::new(VolumeMaterial { field }),
+        }
+    }
+
+    // Sample tentative collisions along the stretches of `r` inside the boundary against the
+    // majorant. A tentative collision is real with the probability of the density over the
+    // majorant, otherwise it is a null collision and the ray goes on. Returns the ray parameter
+    // of the first real collision
+    fn track(&self, r: &Ray, ray_t: &Interval, sampler: &mut dyn Sampler) -> Option<f64> {
+        let max_density = self.field.max_density();
+        if max_density <= 0.0 {
+            return None;
+        }
+        let ray_length = r.direction().length();
+
+        let mut search_start = f64::NEG_INFINITY;
+        while let Some((enter, exit)) =
+            inside_stretch(self.boundary.as_ref(), r, search_start, sampler)
+        {
+            let t_exit = exit.min(ray_t.max);
+            let mut t = enter.max(ray_t.min).max(0.0);
+            loop {
+                t -= (1.0 - sampler.get_1d()).ln() / (max_density * ray_length);
+                if t >= t_exit {
+                    break;
+                }
+                let ratio = self.field.density(&r.at(t)) / max_density;
+                if sampler.get_1d() < ratio {
+                    return Some(t);
+                }
+            }
+
+            if exit >= ray_t.max {
+                break;
+            }
+            search_start = exit + 0.0001;
+        }
+        None
+    }
+}
+
+impl Hittable for HeterogeneousMedium {
+    fn hit(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        let Some(t) = self.track(r, &ray_t, sampler) else {
+            return false;
+        };
+
+        rec.t = t;
+        rec.p = r.at(t);
+        // The volume has no surface, so the normal and side are arbitrary
+        rec.normal = Vec3::new(1.0, 0.0, 0.0);
+        rec.front_face = true;
+        rec.mat = Arc::clone(&self.material);
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+// Isotropic phase function with the albedo and emission of the field at the collision
+struct VolumeMaterial {
+    field: Arc<dyn DensityField>,
+}
+
+impl Material for VolumeMaterial {
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        rec: &HitRecord,
+        srec: &mut ScatterRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> bool {
+        srec.attenuation = self.field.albedo(&rec.p);
+        srec.pdf = Some(Box::new(SpherePdf));
+        true
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    // The collision is an absorption with the probability 1 - albedo, which is when the
+    // absorbing particles' emission is gathered
+    fn emitted(&self, _r_in: &Ray, rec: &HitRecord) -> Color {
+        let albedo = self.field.albedo(&rec.p);
+        let absorption = Color::new(1.0 - albedo.r(), 1.0 - albedo.g(), 1.0 - albedo.b());
+        absorption * self.field.emission(&rec.p)
+    }
+}
+
+#[cfg(test)]
+mod volume_tests {
+    use super::*;
+    use crate::{IndependentSampler, Lambertian, make_box};
+
+    #[test]
+    fn test_tracking_matches_optical_depth() {
+        let mut sampler = IndependentSampler::new(0);
+        // The density goes from 0 to 1 between the voxel centers at z = -0.5 and z = 0.5, and is
+        // constant beyond them, so the optical depth along z through the box is 1
+        let bounds = Aabb::from_points(&Point3::new(-1.0, -1.0, -1.0), &Point3::new(1.0, 1.0, 1.0));
+        let grid = VoxelGrid::new(
+            bounds,
+            [2, 2, 2],
+            vec![0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0],
+        );
+        assert!((grid.density(&Point3::new(0.3, -0.2, 0.0)) - 0.5).abs() < 1e-12);
+        assert_eq!(grid.density(&Point3::new(0.0, 0.0, 2.0)), 0.0);
+
+        let boundary = Arc::new(make_box(
+            &Point3::new(-1.0, -1.0, -1.0),
+            &Point3::new(1.0, 1.0, 1.0),
+            Arc::new(Lambertian::default()),
+        ));
+        let medium = HeterogeneousMedium::new(boundary, Arc::new(grid));
+
+        let ray_count = 20_000;
+        let r = Ray::new(Point3::new(0.1, 0.2, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut transmitted = 0;
+        for _ in 0..ray_count {
+            let mut rec = HitRecord::default();
+            if !medium.hit(
+                &r,
+                Interval::new(0.001, f64::INFINITY),
+                &mut rec,
+                &mut sampler,
+            ) {
+                transmitted += 1;
+            }
+        }
+
+        let expected = (-1.0f64).exp();
+        let fraction = transmitted as f64 / ray_count as f64;
+        assert!((fraction - expected).abs() < 0.02, "{fraction}");
+    }
+
+    #[test]
+    #[should_panic(expected = "VoxelGrid resolution must be positive")]
+    fn test_empty_grid_is_rejected() {
+        let bounds = Aabb::from_points(&Point3::new(0.0, 0.0, 0.0), &Point3::new(1.0, 1.0, 1.0));
+        VoxelGrid::new(bounds, [4, 0, 4], Vec::new());
+    }
+}
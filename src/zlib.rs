@@ -0,0 +1,492 @@
+// Base lengths and extra bits of the length symbols 257..285
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+// Base distances and extra bits of the distance symbols 0..29
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// Order in which the code lengths of the code length alphabet are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+const MAX_BITS: usize = 15;
+
+/// Decompress a zlib stream and check its Adler-32 checksum
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 6 {
+        return Err("zlib stream is too short".to_string());
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 0x0f != 8 {
+        return Err("zlib stream isn't DEFLATE compressed".to_string());
+    }
+    if (u16::from(cmf) << 8 | u16::from(flg)) % 31 != 0 {
+        return Err("corrupt zlib header".to_string());
+    }
+    if flg & 0x20 != 0 {
+        return Err("zlib preset dictionaries aren't supported".to_string());
+    }
+
+    let mut reader = BitReader::new(&data[2..]);
+    let output = inflate(&mut reader)?;
+
+    let checksum_start = 2 + reader.byte_position();
+    let Some(checksum) = data.get(checksum_start..checksum_start + 4) else {
+        return Err("zlib stream is missing its checksum".to_string());
+    };
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&output)
+    {
+        return Err("zlib checksum mismatch".to_string());
+    }
+    Ok(output)
+}
+
+/// Adler-32 checksum of `data`, as used by zlib
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // Sums of 5552 bytes can't overflow before taking the modulo
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+// Reads the bits of a byte stream starting from the least significant bit of every byte
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    bit_buffer: u32,
+    bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader {
+            data,
+            position: 0,
+            bit_buffer: 0,
+            bit_count: 0,
+        }
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        while self.bit_count < count {
+            let Some(&byte) = self.data.get(self.position) else {
+                return Err("DEFLATE stream ended unexpectedly".to_string());
+            };
+            self.position += 1;
+            self.bit_buffer |= u32::from(byte) << self.bit_count;
+            self.bit_count += 8;
+        }
+        let value = self.bit_buffer & ((1u64 << count) - 1) as u32;
+        self.bit_buffer >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    // Drop the bits left in the current byte
+    fn align_to_byte(&mut self) {
+        self.bit_buffer = 0;
+        self.bit_count = 0;
+    }
+
+    // Position of the first byte which hasn't been read yet
+    fn byte_position(&self) -> usize {
+        self.position - (self.bit_count / 8) as usize
+    }
+}
+
+// Canonical Huffman code stored as the count of codes of every length and the symbols ordered
+// by their codes
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, String> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // Reject codes which use more codes than available
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return Err("over-subscribed Huffman code".to_string());
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    // Read a code bit by bit, comparing it against the first code of every length
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for length in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = i32::from(self.counts[length]);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+fn inflate(reader: &mut BitReader) -> Result<Vec<u8>, String> {
+    let mut output = Vec::new();
+    loop {
+        let is_final = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => inflate_stored(reader, &mut output)?,
+            1 => {
+                let (literal_code, distance_code) = fixed_codes()?;
+                inflate_block(reader, &mut output, &literal_code, &distance_code)?;
+            }
+            2 => {
+                let (literal_code, distance_code) = dynamic_codes(reader)?;
+                inflate_block(reader, &mut output, &literal_code, &distance_code)?;
+            }
+            _ => return Err("invalid DEFLATE block type".to_string()),
+        }
+        if is_final {
+            return Ok(output);
+        }
+    }
+}
+
+fn inflate_stored(reader: &mut BitReader, output: &mut Vec<u8>) -> Result<(), String> {
+    reader.align_to_byte();
+    let length = reader.bits(16)?;
+    let complement = reader.bits(16)?;
+    if length != !complement & 0xffff {
+        return Err("corrupt stored DEFLATE block".to_string());
+    }
+    let start = reader.position;
+    let Some(bytes) = reader.data.get(start..start + length as usize) else {
+        return Err("DEFLATE stream ended unexpectedly".to_string());
+    };
+    output.extend_from_slice(bytes);
+    reader.position += length as usize;
+    Ok(())
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literal_code: &Huffman,
+    distance_code: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = literal_code.decode(reader)? as usize;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let symbol = symbol - 257;
+                let length = LENGTH_BASE[symbol] as usize
+                    + reader.bits(u32::from(LENGTH_EXTRA[symbol]))? as usize;
+
+                let symbol = distance_code.decode(reader)? as usize;
+                if symbol >= DIST_BASE.len() {
+                    return Err("invalid DEFLATE distance symbol".to_string());
+                }
+                let distance = DIST_BASE[symbol] as usize
+                    + reader.bits(u32::from(DIST_EXTRA[symbol]))? as usize;
+                if distance > output.len() {
+                    return Err("DEFLATE distance is too far back".to_string());
+                }
+
+                // Copy byte by byte, as the copied range may overlap the bytes being written
+                let start = output.len() - distance;
+                for k in 0..length {
+                    output.push(output[start + k]);
+                }
+            }
+            _ => return Err("invalid DEFLATE literal/length symbol".to_string()),
+        }
+    }
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman), String> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err("too many DEFLATE codes".to_string());
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    // The literal/length and distance code lengths are run-length encoded together
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code_length_code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                if index == 0 {
+                    return Err("DEFLATE code length repeat without a previous length".to_string());
+                }
+                (lengths[index - 1], 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        if index + repeat > lengths.len() {
+            return Err("too many DEFLATE code lengths".to_string());
+        }
+        lengths[index..index + repeat].fill(value);
+        index += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err("DEFLATE block has no end code".to_string());
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count])?,
+        Huffman::new(&lengths[literal_count..])?,
+    ))
+}
+
+/// Compress `data` into a zlib stream
+///
+/// Repeated strings are replaced by references to earlier copies (LZ77) and encoded with the
+/// fixed Huffman codes of DEFLATE. Data which doesn't compress is stored as is
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32 KiB window and the default compression level
+    let mut output = vec![0x78, 0x9c];
+
+    let compressed = deflate_fixed(data);
+    // Stored blocks cost 5 bytes every 65535 bytes
+    let stored_size = data.len() + 5 * data.len().div_ceil(65535).max(1);
+    if compressed.len() < stored_size {
+        output.extend_from_slice(&compressed);
+    } else {
+        deflate_stored(data, &mut output);
+    }
+
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+    output
+}
+
+// Longest distance and length of the strings which can be copied
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+// Count of earlier positions tried when looking for the longest match
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+// Writes bits to a byte stream starting from the least significant bit of every byte
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit_buffer: u64,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter {
+            bytes: Vec::new(),
+            bit_buffer: 0,
+            bit_count: 0,
+        }
+    }
+
+    fn bits(&mut self, value: u32, count: u32) {
+        self.bit_buffer |= u64::from(value) << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.bytes.push(self.bit_buffer as u8);
+            self.bit_buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    // Huffman codes are stored starting from their most significant bit
+    fn code(&mut self, code: u32, length: u32) {
+        self.bits(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.bytes.push(self.bit_buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn deflate_stored(data: &[u8], output: &mut Vec<u8>) {
+    let mut chunks = data.chunks(65535).peekable();
+    if chunks.peek().is_none() {
+        // An empty final stored block
+        output.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(chunk) = chunks.next() {
+        let is_final = chunks.peek().is_none();
+        let length = chunk.len() as u16;
+        output.push(u8::from(is_final));
+        output.extend_from_slice(&length.to_le_bytes());
+        output.extend_from_slice(&(!length).to_le_bytes());
+        output.extend_from_slice(chunk);
+    }
+}
+
+fn deflate_fixed(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    // A single final block using the fixed codes
+    writer.bits(1, 1);
+    writer.bits(1, 2);
+
+    // Most recent position of every hash of 3 bytes, and the previous position with the same
+    // hash for every position in the window
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; WINDOW_SIZE];
+    let hash = |position: usize| {
+        let value = u32::from(data[position]) << 16
+            | u32::from(data[position + 1]) << 8
+            | u32::from(data[position + 2]);
+        (value.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+    };
+    let insert = |position: usize, head: &mut [usize], previous: &mut [usize]| {
+        if position + MIN_MATCH <= data.len() {
+            let h = hash(position);
+            previous[position % WINDOW_SIZE] = head[h];
+            head[h] = position;
+        }
+    };
+
+    let mut position = 0;
+    while position < data.len() {
+        let (mut best_length, mut best_distance) = (0, 0);
+        if position + MIN_MATCH <= data.len() {
+            let max_length = MAX_MATCH.min(data.len() - position);
+            let mut candidate = head[hash(position)];
+            for _ in 0..MAX_CHAIN {
+                if candidate == usize::MAX || position - candidate > WINDOW_SIZE {
+                    break;
+                }
+                let length = data[candidate..]
+                    .iter()
+                    .zip(&data[position..position + max_length])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best_length {
+                    (best_length, best_distance) = (length, position - candidate);
+                    if length == max_length {
+                        break;
+                    }
+                }
+                let next = previous[candidate % WINDOW_SIZE];
+                // Stop at entries of the chain overwritten by newer positions
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+            }
+        }
+
+        if best_length >= MIN_MATCH {
+            write_length(&mut writer, best_length);
+            write_distance(&mut writer, best_distance);
+            for p in position..position + best_length {
+                insert(p, &mut head, &mut previous);
+            }
+            position += best_length;
+        } else {
+            write_literal(&mut writer, u16::from(data[position]));
+            insert(position, &mut head, &mut previous);
+            position += 1;
+        }
+    }
+
+    write_literal(&mut writer, 256);
+    writer.finish()
+}
+
+// Write a literal/length symbol with the fixed literal/length code
+fn write_literal(writer: &mut BitWriter, symbol: u16) {
+    let symbol = u32::from(symbol);
+    match symbol {
+        0..=143 => writer.code(0x30 + symbol, 8),
+        144..=255 => writer.code(0x190 + symbol - 144, 9),
+        256..=279 => writer.code(symbol - 256, 7),
+        _ => writer.code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn write_length(writer: &mut BitWriter, length: usize) {
+    let index = LENGTH_BASE
+        .iter()
+        .rposition(|&base| base as usize <= length)
+        .unwrap();
+    write_literal(writer, 257 + index as u16);
+    writer.bits(
+        (length - LENGTH_BASE[index] as usize) as u32,
+        u32::from(LENGTH_EXTRA[index]),
+    );
+}
+
+fn write_distance(writer: &mut BitWriter, distance: usize) {
+    let index = DIST_BASE
+        .iter()
+        .rposition(|&base| base as usize <= distance)
+        .unwrap();
+    // The fixed distance code has 5 bits for every symbol
+    writer.code(index as u32, 5);
+    writer.bits(
+        (distance - DIST_BASE[index] as usize) as u32,
+        u32::from(DIST_EXTRA[index]),
+    );
+}